@@ -2,4 +2,23 @@ console.log('Loaded!');
 
 function test() {
     console.log("Working");
-}
\ No newline at end of file
+}
+
+// Lines read from stderr are marked so that JVM errors stand out from the server's log
+async function updateConsole() {
+    let output = await (await fetch('/api/console')).json();
+    let stderr = new Set(output.stderr);
+    let log = document.getElementById('console');
+    log.replaceChildren(...Object.keys(output.chat).sort((a, b) => a - b).map(num => {
+        let line = document.createElement('div');
+        if (stderr.has(Number(num))) {
+            line.className = 'stderr';
+            line.textContent = `[stderr] ${output.chat[num]}`;
+        } else {
+            line.textContent = output.chat[num];
+        }
+        return line;
+    }));
+}
+
+setInterval(() => updateConsole().catch(() => {}), 1000);
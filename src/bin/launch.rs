@@ -1,50 +1,96 @@
 //! Crate wide documentation?
 extern crate minecraft_monitor as mon;
-use mon::functions::configuration::{determine_config, Verbosity};
+use mon::functions::configuration::{determine_config, Configuration, Verbosity};
 use mon::functions::minecraft_related::*;
+use mon::functions::protocol::{run_placeholder, run_query_responder};
+use mon::functions::rcon::run_rcon_server;
+use mon::functions::scheduler::{run_scheduler, unix_time};
 use mon::functions::shared_data::*;
 use mon::functions::web_server::handle_connections;
 use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
 use std::process::{Command, Stdio};
-use std::sync::mpsc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
-use std::{env, net::Ipv4Addr};
+use std::time::{Duration, Instant};
+use std::env;
 
 fn main() {
     // Create a writer that will write content to a file, only interactions that happen from this software will be logged, Minecraft logs itself
-    let (
-        address,
-        port,
-        web_index,
-        root_location,
-        jar_name,
-        gen_args,
-        min_ram,
-        max_ram,
-        web_log,   // If interactions with the webserver should be saved to a log
-        verbosity, // Currently this is not set up
-    ) = determine_config(env::args().collect()).unwrap();
+    let config = determine_config(env::args().collect()).unwrap();
 
-    env::set_current_dir(Path::new(&root_location)).unwrap();
+    env::set_current_dir(Path::new(&config.root_location)).unwrap();
     // Logger::new(); // TODO Logging will be hard
 
     let shared_data = ServerSharedData::new();
+    *shared_data.schedule.lock().unwrap() = config.schedule.clone();
+    *shared_data.startup_history.lock().unwrap() = load_startup_history();
+    *shared_data.chat.lock().unwrap() = load_chat_history();
+    *shared_data.player_history.lock().unwrap() = load_player_history();
+    *shared_data.performance.lock().unwrap() = load_performance_history();
+    // Commands are queued here for whichever launch is running, the web server and API stay up between launches
+    let (web_sender, web_receiver) = mpsc::channel::<ConsoleCommand>();
+    let web_receiver = Arc::new(Mutex::new(web_receiver));
+    let shared_data_web = shared_data.clone();
+    let web_sender_clone = web_sender.clone();
+    let web_config = config.clone();
+    let web_handle = thread::spawn(move || {
+        handle_connections(shared_data_web, web_sender_clone, web_config).unwrap()
+    });
+    // The responder outlives each launch, answering Query while the server is down between them
+    let query_handle = if config.query_responder {
+        let query_data = shared_data.clone();
+        Some(thread::spawn(move || run_query_responder(query_data)))
+    } else {
+        None
+    };
+    // Like the web server, RCON clients stay connected while the server restarts
+    let rcon_handle = if config.rcon_port > 0 {
+        let shared_data_rcon = shared_data.clone();
+        let rcon_sender = web_sender.clone();
+        let rcon_config = config.clone();
+        Some(thread::spawn(move || {
+            if let Err(error) = run_rcon_server(shared_data_rcon, rcon_sender, rcon_config) {
+                println!("\x1b[0;31m[Error]:\x1b[0m The monitor's RCON could not listen: {}", error);
+            }
+        }))
+    } else {
+        None
+    };
+    let placeholder_handle = if config.placeholder {
+        let placeholder_data = shared_data.clone();
+        let placeholder_config = config.clone();
+        Some(thread::spawn(move || run_placeholder(placeholder_data, placeholder_config)))
+    } else {
+        None
+    };
     // call launch with shared data
     loop {
-        launch(
-            shared_data.clone(),
-            address.clone(),
-            port.clone(),
-            web_index.clone(),
-            jar_name.clone(),
-            gen_args.clone(),
-            min_ram.clone(),
-            max_ram.clone(),
-            web_log.clone(),
-            verbosity.clone(),
-        );
+        let crashes = shared_data.crashes.lock().unwrap().len();
+        launch(shared_data.clone(), config.clone(), web_sender.clone(), web_receiver.clone());
+        if shared_data.crashes.lock().unwrap().len() > crashes {
+            let backoff = crash_backoff(
+                &shared_data.crashes.lock().unwrap(),
+                unix_time(),
+                config.crash_limit,
+                config.crash_window,
+                config.crash_backoff,
+            );
+            match backoff {
+                Some(delay) => {
+                    println!("Restarting the Minecraft server in {} seconds after a crash", delay);
+                    thread::sleep(Duration::from_secs(delay));
+                }
+                None => {
+                    println!(
+                        "The Minecraft server crashed {} times within {} minutes, it will not be restarted",
+                        config.crash_limit, config.crash_window
+                    );
+                    *shared_data.gen_state.lock().unwrap() = GeneralState::ShutDown;
+                }
+            }
+        }
         let mut state = shared_data.gen_state.lock().unwrap();
         if *state == GeneralState::Restart {
             *state = GeneralState::Running;
@@ -56,62 +102,54 @@ fn main() {
             break;
         } // If it does not shutdown then it restarts, it shouldn't reach this point without being either shutdown or restart
     }
+    web_handle.join().unwrap();
+    if config.verbosity == Verbosity::Web || config.verbosity == Verbosity::MineWeb {
+        println!("Web Server Thread Closed");
+    }
+    if let Some(handle) = rcon_handle {
+        handle.join().unwrap();
+    }
+    if let Some(handle) = query_handle {
+        handle.join().unwrap();
+    }
+    if let Some(handle) = placeholder_handle {
+        handle.join().unwrap();
+    }
 }
 
 fn launch(
     shared_data: ServerSharedData,
-    address: Ipv4Addr,
-    port: u16,
-    web_index: String,
-    jar_name: String,
-    gen_args: Option<String>,
-    min_ram: String,
-    max_ram: String,
-    web_log: bool,
-    verbosity: Verbosity,
+    config: Configuration,
+    web_sender: Sender<ConsoleCommand>,
+    web_receiver: Arc<Mutex<Receiver<ConsoleCommand>>>,
 ) {
-    let (web_sender, web_receiver) = mpsc::channel::<String>();
-    let shared_data_web = shared_data.clone();
-    let web_sender_clone = web_sender.clone();
-    let web_handle = thread::spawn(move || {
-        handle_connections(
-            shared_data_web,
-            web_sender_clone,
-            address,
-            port,
-            web_index,
-            verbosity,
-        )
-        .unwrap()
+    let verbosity = config.verbosity;
+    // Commands left over from before the last launch stopped were meant for that server
+    while web_receiver.lock().unwrap().try_recv().is_ok() {}
+
+    let shared_data_scheduler = shared_data.clone();
+    let scheduler_sender = web_sender.clone();
+    let scheduler_config = config.clone();
+    let scheduler_handle = thread::spawn(move || {
+        run_scheduler(shared_data_scheduler, scheduler_sender, scheduler_config)
     });
 
-    let mut child;
-    if gen_args == None {
-        child = Command::new("java")
-            .args(&[
-                format!("-Xms{}", min_ram).as_str(),
-                format!("-Xmx{}", max_ram).as_str(),
-                "-XX:+UseG1GC",
-                "-jar",
-                format!("{}", jar_name).as_str(),
-                "nogui",
-            ])
-            .stdout(Stdio::piped())
-            .stdin(Stdio::piped())
-            .spawn()
-            .expect("Error starting server, refer to console for more details.");
-    } else {
-        // let args = Vec::new();
-        let args = gen_args.unwrap();
-        let split_args = args.split(" ").collect::<Vec<&str>>();
-        // let test = args.clone();
-        child = Command::new("java")
-            .args(split_args)
-            .stdout(Stdio::piped())
-            .stdin(Stdio::piped())
-            .spawn()
-            .expect("Error starting server, refer to console for more details.");
-    }
+    let args = match &config.gen_args {
+        None => config.flavor.launch_arguments(&config),
+        Some(args) => args.split(' ').map(|arg| arg.to_string()).collect(),
+    };
+    *shared_data.startup.lock().unwrap() = StartupProgress::new();
+    // The placeholder gives back the game port, and can not take it again until the process has been stored
+    let mut placeholder = shared_data.placeholder.lock().unwrap();
+    *placeholder = None;
+    let mut child = Command::new("java")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .stdin(Stdio::piped())
+        .spawn()
+        .expect("Error starting server, refer to console for more details.");
+    let mut server_in = child.stdin.take().expect("[Error] Failed to open server input");
 
     // Output section
     let mut mcserver_out = BufReader::new(
@@ -120,13 +158,28 @@ fn launch(
             .take()
             .expect("[Error] Failed to open server output"),
     );
+    let mut mcserver_err = BufReader::new(
+        child
+            .stderr
+            .take()
+            .expect("[Error] Failed to open server error output"),
+    );
+
+    // The process is kept in the shared data so that it can be stopped by force if needed
+    *shared_data.child.lock().unwrap() = Some(child);
+    drop(placeholder);
+    {
+        let mut counters = shared_data.counters.lock().unwrap();
+        counters.launches += 1;
+        counters.launched = Some(Instant::now());
+    }
 
     let shared_data_output = shared_data.clone();
-    let output_verbosity = verbosity.clone(); // This might not be needed because COPY has been derived now
     let output_sender = web_sender.clone();
+    let output_config = config.clone();
     let output_handle = thread::spawn(move || {
-        let mut line_num: u32 = 0;
         let output_sender_thread = output_sender.clone();
+        let mut parser = output_config.flavor.log_parser();
         loop {
             // let output_sender3 = output_sender2.clone();
             {
@@ -135,7 +188,10 @@ fn launch(
                 // println!("Checking state in output");
                 if *mc_state == MinecraftServerState::Off {
                     let mut state = shared_data_output.gen_state.lock().unwrap();
-                    if *state == GeneralState::Restart || *state == GeneralState::ShutDown {
+                    if *state == GeneralState::Restart
+                        || *state == GeneralState::ShutDown
+                        || *state == GeneralState::Stopping
+                    {
                         break;
                     } else if *state == GeneralState::Running {
                         *state = GeneralState::Restart;
@@ -147,32 +203,53 @@ fn launch(
                     break;
                 }
             }
-            let chat = shared_data_output.server_output.clone();
             let mut buf = Vec::new();
-            mcserver_out.read_until(b'\n', &mut buf).unwrap();
-            let line = String::from_utf8(buf).unwrap();
-            if line != "".to_string() {
-                let content = &line.clone()[17..];
-                if line != "" {
-                    let mut term = chat.lock().unwrap();
-                    if output_verbosity == Verbosity::Mine || verbosity == Verbosity::MineWeb {
-                        print!("\x1b[0;36m[Console]:\x1b[0m {}", line);
-                    }
-                    term.push_front((line_num, line));
-                    while term.len() > 1000 {
-                        term.pop_back();
-                    }
-                }
-                // Check if a player has joined
-                server_output_scanning(content, shared_data_output.clone(), &output_sender_thread);
-                line_num += 1;
+            // Nothing left to read means that the server has closed its output because it is exiting
+            if mcserver_out.read_until(b'\n', &mut buf).unwrap_or(0) == 0 {
+                handle_server_exit(&shared_data_output);
+                break;
+            }
+            let line = String::from_utf8_lossy(&buf).to_string();
+            let log_line = parser.parse(&line);
+            if verbosity == Verbosity::Mine || verbosity == Verbosity::MineWeb {
+                print!("\x1b[0;36m[Console]:\x1b[0m {}", line);
+            }
+            shared_data_output.push_output(line, OutputStream::Stdout);
+            shared_data_output.heartbeats.lock().unwrap().output_read = Some(Instant::now());
+            // Check if a player has joined
+            server_output_scanning(
+                &log_line,
+                shared_data_output.clone(),
+                &output_sender_thread,
+                output_config.flavor.as_ref(),
+            );
+        }
+    });
+
+    // Error section, the JVM writes crashes and stack traces here instead of the server log
+    let shared_data_error = shared_data.clone();
+    let error_sender = web_sender.clone();
+    let error_config = config.clone();
+    let error_handle = thread::spawn(move || {
+        let mut parser = error_config.flavor.log_parser();
+        loop {
+            let mut buf = Vec::new();
+            // The stream closes when the server exits, the output thread handles the exit itself
+            if mcserver_err.read_until(b'\n', &mut buf).unwrap_or(0) == 0 {
+                break;
             }
+            let line = String::from_utf8_lossy(&buf).to_string();
+            let log_line = parse_stderr_line(&mut parser, &line);
+            if verbosity == Verbosity::Mine || verbosity == Verbosity::MineWeb {
+                print!("\x1b[0;31m[Stderr]:\x1b[0m {}", line);
+            }
+            shared_data_error.push_output(line, OutputStream::Stderr);
+            server_output_scanning(&log_line, shared_data_error.clone(), &error_sender, error_config.flavor.as_ref());
         }
     });
 
     // Input section
     let shared_data_input = shared_data.clone();
-    let input_verbosity = verbosity.clone();
     let input_handle = thread::spawn(move || {
         loop {
             // let verbosity = verbosity.clone();
@@ -182,7 +259,10 @@ fn launch(
                 if *mc_state == MinecraftServerState::Off {
                     let mut state = shared_data_input.gen_state.lock().unwrap();
                     println!("Checking state in input");
-                    if *state == GeneralState::Restart || *state == GeneralState::ShutDown {
+                    if *state == GeneralState::Restart
+                        || *state == GeneralState::ShutDown
+                        || *state == GeneralState::Stopping
+                    {
                         break;
                     } else if *state == GeneralState::Running {
                         *state = GeneralState::Restart;
@@ -194,16 +274,26 @@ fn launch(
                     break;
                 }
             }
+            shared_data_input.heartbeats.lock().unwrap().input = Some(Instant::now());
             // Sleeping per the tick rate, this might be slightly extreme for the purposes of this application
-            match web_receiver.recv_timeout(Duration::from_millis(50)) {
-                Ok(mut cmd) => {
-                    cmd = cmd + "\n";
-                    if input_verbosity == Verbosity::Mine || input_verbosity == Verbosity::MineWeb {
-                        print!("\x1b[0;35m[Command]:\x1b[0m {}", cmd);
+            let received = web_receiver.lock().unwrap().recv_timeout(Duration::from_millis(50));
+            match received {
+                Ok(cmd) => {
+                    let line = cmd.command + "\n";
+                    if verbosity == Verbosity::Mine || verbosity == Verbosity::MineWeb {
+                        print!("\x1b[0;35m[Command]:\x1b[0m {}", line);
                     }
-                    {
-                        let server_in = child.stdin.as_mut().unwrap();
-                        server_in.write_all(cmd.as_bytes()).unwrap();
+                    // Anything printed from this point on could be a response to the command
+                    let next_line = shared_data_input.next_line_number();
+                    if server_in.write_all(line.as_bytes()).is_err() {
+                        println!("\x1b[0;31m[Error]:\x1b[0m The Minecraft server is not accepting input");
+                        continue;
+                    }
+                    shared_data_input.counters.lock().unwrap().commands_sent += 1;
+                    shared_data_input.heartbeats.lock().unwrap().command_written = Some(Instant::now());
+                    if let Some(written) = cmd.written {
+                        // The requester might have already stopped waiting
+                        let _ = written.send(next_line);
                     }
                 }
                 Err(_) => {
@@ -221,8 +311,24 @@ fn launch(
     if verbosity == Verbosity::Mine || verbosity == Verbosity::MineWeb {
         println!("Minecraft Server Input Thread Closed");
     }
-    web_handle.join().unwrap();
-    if verbosity == Verbosity::Web || verbosity == Verbosity::MineWeb {
-        println!("Web Server Thread Closed");
+    error_handle.join().unwrap();
+    if verbosity == Verbosity::Mine || verbosity == Verbosity::MineWeb {
+        println!("Minecraft Server Error Thread Closed");
+    }
+    scheduler_handle.join().unwrap();
+    // Make sure the old process has exited before another is launched
+    // Taken out first, so that the lock is not held while waiting
+    let child = shared_data.child.lock().unwrap().take();
+    if let Some(mut child) = child {
+        let _ = child.wait();
+    }
+    shared_data.counters.lock().unwrap().launched = None;
+    {
+        // The threads that beat these have stopped on purpose, they are not stuck
+        let mut heartbeats = shared_data.heartbeats.lock().unwrap();
+        heartbeats.scheduler = None;
+        heartbeats.input = None;
     }
+    // Nobody is online once the server has stopped, however it stopped
+    end_player_sessions(&shared_data, "Server stopped");
 }
@@ -1,29 +1,60 @@
+use super::minecraft_related::{detect_flavor, flavor_named, ServerFlavor};
+use super::scheduler::ScheduledTask;
 use std::{
     fs::{self, File},
     io::{BufRead, BufReader, Error},
     net::Ipv4Addr,
     path::Path,
     process::Command,
+    sync::Arc,
     thread,
 };
 
-pub fn determine_config(
-    args: Vec<String>,
-) -> Result<
-    (
-        Ipv4Addr,
-        u16,
-        String,
-        String,
-        String,
-        Option<String>,
-        String,
-        String,
-        bool,
-        Verbosity,
-    ),
-    Error,
-> {
+/// The settings that the monitor runs with, determined from the command line arguments and the configuration file.
+#[derive(Clone)]
+pub struct Configuration {
+    pub address: Ipv4Addr,
+    pub port: u16,
+    pub web_index: String,
+    pub root_location: String,
+    pub jar_name: String,
+    pub gen_args: Option<String>,
+    pub min_ram: String,
+    pub max_ram: String,
+    pub web_log: bool,
+    pub verbosity: Verbosity,
+    /// Seconds to wait for the server to stop before the process is terminated.
+    pub stop_timeout: u64,
+    /// Seconds before a delayed shutdown or restart at which players are warned, largest first.
+    pub countdown_warnings: Vec<u64>,
+    pub schedule: Vec<ScheduledTask>,
+    /// Where scheduled backups are saved, relative to the server directory.
+    pub backup_location: String,
+    /// The number of crashes within `crash_window` minutes after which the server is no longer restarted.
+    pub crash_limit: u32,
+    pub crash_window: u64,
+    /// Seconds to wait before restarting after a crash, doubled for each other recent crash.
+    pub crash_backoff: u64,
+    /// Seconds between checking the players online against the server's `list`, 0 to never check.
+    pub list_interval: u64,
+    /// Seconds between measuring the server's ticks per second, 0 to never measure.
+    pub performance_interval: u64,
+    /// The port that the monitor's own RCON listens on, 0 to not listen.
+    pub rcon_port: u16,
+    pub rcon_password: String,
+    /// Whether the monitor answers Query requests while the server is down or starting.
+    pub query_responder: bool,
+    /// Whether the monitor holds the game port while the server is down, answering pings and turning away logins.
+    pub placeholder: bool,
+    /// The text shown in server lists and to players who join while the placeholder holds the port, `{eta}` is
+    /// replaced with an estimate of when the server will be back.
+    pub placeholder_motd: String,
+    pub placeholder_message: String,
+    /// The kind of server that is run, either configured or detected from the jar.
+    pub flavor: Arc<dyn ServerFlavor>,
+}
+
+pub fn determine_config(args: Vec<String>) -> Result<Configuration, Error> {
     // Process args
     // Check environment
     // Make sure that the public directory exists, if not, check with user, then download.
@@ -41,6 +72,23 @@ pub fn determine_config(
     let mut verbosity = None;
     let mut download_public = None;
     let mut download_config = None;
+    let mut stop_timeout = None;
+    let mut countdown_warnings = None;
+    let mut schedule: Option<Vec<String>> = None;
+    let mut file_schedule = Vec::new();
+    let mut backup_location = None;
+    let mut crash_limit = None;
+    let mut crash_window = None;
+    let mut crash_backoff = None;
+    let mut flavor = None;
+    let mut list_interval = None;
+    let mut performance_interval = None;
+    let mut rcon_port = None;
+    let mut rcon_password = None;
+    let mut query_responder = None;
+    let mut placeholder = None;
+    let mut placeholder_motd = None;
+    let mut placeholder_message = None;
 
     match args.len() {
         0 => panic!("No file specified, this might not be needed"),
@@ -85,6 +133,47 @@ pub fn determine_config(
                         "--args" | "-x" => {
                             gen_args = Some(verify_general_args(args[index + 1].clone()))
                         }
+                        "--stop_timeout" | "-t" => {
+                            stop_timeout = Some(verify_stop_timeout(args[index + 1].clone()))
+                        }
+                        "--countdown_warnings" | "-n" => {
+                            countdown_warnings =
+                                Some(verify_countdown_warnings(args[index + 1].clone()))
+                        }
+                        "--schedule" | "-s" => {
+                            // Can be given more than once, any schedules given as args replace those in the file
+                            schedule
+                                .get_or_insert_with(Vec::new)
+                                .push(args[index + 1].clone())
+                        }
+                        "--backup_location" | "-b" => {
+                            backup_location = Some(args[index + 1].clone())
+                        }
+                        "--crash_limit" | "-e" => {
+                            crash_limit = Some(verify_crash_limit(args[index + 1].clone()))
+                        }
+                        "--crash_window" | "-E" => {
+                            crash_window = Some(verify_crash_window(args[index + 1].clone()))
+                        }
+                        "--crash_backoff" | "-B" => {
+                            crash_backoff = Some(verify_crash_backoff(args[index + 1].clone()))
+                        }
+                        "--flavor" | "-f" => flavor = Some(verify_flavor(args[index + 1].clone())),
+                        "--list_interval" | "-L" => {
+                            list_interval = Some(verify_list_interval(args[index + 1].clone()))
+                        }
+                        "--performance_interval" | "-P" => {
+                            performance_interval =
+                                Some(verify_performance_interval(args[index + 1].clone()))
+                        }
+                        "--rcon_port" | "-r" => rcon_port = Some(verify_rcon_port(args[index + 1].clone())),
+                        "--rcon_password" | "-R" => rcon_password = Some(args[index + 1].clone()),
+                        "--query_responder" | "-q" => {
+                            query_responder = Some(verify_query_responder(args[index + 1].clone()))
+                        }
+                        "--placeholder" | "-y" => placeholder = Some(verify_placeholder(args[index + 1].clone())),
+                        "--placeholder_motd" | "-g" => placeholder_motd = Some(args[index + 1].clone()),
+                        "--placeholder_message" | "-G" => placeholder_message = Some(args[index + 1].clone()),
                         _ => panic!("Invalid parameter found, found: {}", *arg),
                     }
                 } else if index % 2 == 1 && index != 0 {
@@ -98,7 +187,7 @@ pub fn determine_config(
         }
     };
     // Command Line Arguments should have been parsed and error checked
-    if download_config == None {
+    if download_config.is_none() {
         // If not set, do not download
         download_config = Some(false);
     }
@@ -124,7 +213,7 @@ pub fn determine_config(
     let reader = BufReader::new(File::open("config.conf").unwrap());
     for (index, line) in reader.lines().enumerate() {
         let line = line.unwrap(); // Not sure what errors could happen here
-        if line != "" && !(&line[0..1] == "#") {
+        if !line.is_empty() && &line[0..1] != "#" {
             // Comments and blank lines are ignored
             let equal = match line.find("=") {
                     Some(loc) => loc,
@@ -133,67 +222,157 @@ pub fn determine_config(
             // println!("0..=: {}, =..:{}", &line[0..equal], &line[equal + 2..line.len() - 1]);
             match &line[0..equal] {
                 "server_location" => {
-                    if root_location == None {
+                    if root_location.is_none() {
                         root_location =
                             Some(verify_location(line[equal + 2..line.len() - 1].to_string()));
                     }
                 }
                 "webserver_address" => {
-                    if address == None {
+                    if address.is_none() {
                         address = Some(verify_address(line[equal + 2..line.len() - 1].to_string()));
                     }
                 }
                 "webserver_port" => {
-                    if port == None {
+                    if port.is_none() {
                         port = Some(verify_port(line[equal + 2..line.len() - 1].to_string()));
                     }
                 }
                 "webserver_index" => {
-                    if web_index == None {
+                    if web_index.is_none() {
                         web_index = Some(line[equal + 2..line.len() - 1].to_string());
                     }
                 }
                 "generic_args" => {
-                    if gen_args == None {
+                    if gen_args.is_none() {
                         gen_args = Some(verify_general_args(
                             line[equal + 2..line.len() - 1].to_string(),
                         ));
                     }
                 }
                 "server_jar" => {
-                    if jar_name == None {
+                    if jar_name.is_none() {
                         jar_name = Some(verify_jar(line[equal + 2..line.len() - 1].to_string()));
                     }
                 }
                 "minimum_ram" => {
-                    if min_ram == None {
+                    if min_ram.is_none() {
                         min_ram = Some(verify_min_ram(line[equal + 2..line.len() - 1].to_string()));
                     }
                 }
                 "maximum_ram" => {
-                    if max_ram == None {
+                    if max_ram.is_none() {
                         max_ram = Some(verify_max_ram(line[equal + 2..line.len() - 1].to_string()));
                     }
                 }
                 "download_public" => {
-                    if download_public == None {
+                    if download_public.is_none() {
                         download_public = Some(verify_download_web(
                             line[equal + 2..line.len() - 1].to_string(),
                         ));
                     }
                 }
                 "log_web" => {
-                    if web_log == None {
+                    if web_log.is_none() {
                         web_log = Some(verify_web_log(line[equal + 2..line.len() - 1].to_string()));
                     }
                 }
                 "verbosity" => {
-                    if verbosity == None {
+                    if verbosity.is_none() {
                         verbosity = Some(verify_verbosity(
                             line[equal + 2..line.len() - 1].to_string(),
                         ));
                     }
                 }
+                "stop_timeout" => {
+                    if stop_timeout.is_none() {
+                        stop_timeout = Some(verify_stop_timeout(
+                            line[equal + 2..line.len() - 1].to_string(),
+                        ));
+                    }
+                }
+                "countdown_warnings" => {
+                    if countdown_warnings.is_none() {
+                        countdown_warnings = Some(verify_countdown_warnings(
+                            line[equal + 2..line.len() - 1].to_string(),
+                        ));
+                    }
+                }
+                "schedule" => file_schedule.push(line[equal + 2..line.len() - 1].to_string()),
+                "backup_location" => {
+                    if backup_location.is_none() {
+                        backup_location = Some(line[equal + 2..line.len() - 1].to_string());
+                    }
+                }
+                "crash_limit" => {
+                    if crash_limit.is_none() {
+                        crash_limit = Some(verify_crash_limit(
+                            line[equal + 2..line.len() - 1].to_string(),
+                        ));
+                    }
+                }
+                "crash_window" => {
+                    if crash_window.is_none() {
+                        crash_window = Some(verify_crash_window(
+                            line[equal + 2..line.len() - 1].to_string(),
+                        ));
+                    }
+                }
+                "crash_backoff" => {
+                    if crash_backoff.is_none() {
+                        crash_backoff = Some(verify_crash_backoff(
+                            line[equal + 2..line.len() - 1].to_string(),
+                        ));
+                    }
+                }
+                "list_interval" => {
+                    if list_interval.is_none() {
+                        list_interval = Some(verify_list_interval(
+                            line[equal + 2..line.len() - 1].to_string(),
+                        ));
+                    }
+                }
+                "performance_interval" => {
+                    if performance_interval.is_none() {
+                        performance_interval = Some(verify_performance_interval(
+                            line[equal + 2..line.len() - 1].to_string(),
+                        ));
+                    }
+                }
+                "rcon_port" => {
+                    if rcon_port.is_none() {
+                        rcon_port = Some(verify_rcon_port(line[equal + 2..line.len() - 1].to_string()));
+                    }
+                }
+                "rcon_password" => {
+                    if rcon_password.is_none() {
+                        rcon_password = Some(line[equal + 2..line.len() - 1].to_string());
+                    }
+                }
+                "query_responder" => {
+                    if query_responder.is_none() {
+                        query_responder = Some(verify_query_responder(line[equal + 2..line.len() - 1].to_string()));
+                    }
+                }
+                "placeholder" => {
+                    if placeholder.is_none() {
+                        placeholder = Some(verify_placeholder(line[equal + 2..line.len() - 1].to_string()));
+                    }
+                }
+                "placeholder_motd" => {
+                    if placeholder_motd.is_none() {
+                        placeholder_motd = Some(line[equal + 2..line.len() - 1].to_string());
+                    }
+                }
+                "placeholder_message" => {
+                    if placeholder_message.is_none() {
+                        placeholder_message = Some(line[equal + 2..line.len() - 1].to_string());
+                    }
+                }
+                "server_flavor" => {
+                    if flavor.is_none() {
+                        flavor = Some(verify_flavor(line[equal + 2..line.len() - 1].to_string()));
+                    }
+                }
                 _ => {
                     panic!("Unexpected config found on line: {}, found: {}. If this line was intended to be a comment please prefix the line with a '#'", index, line)
                 }
@@ -202,39 +381,87 @@ pub fn determine_config(
     }
 
     // If a parameter has not been set then use the default
-    if root_location == None {
+    if root_location.is_none() {
         root_location = Some("./server".to_string());
     }
-    if address == None {
+    if address.is_none() {
         address = Some("127.0.0.1".parse::<Ipv4Addr>().unwrap());
     }
-    if port == None {
+    if port.is_none() {
         port = Some(8000);
     }
-    if web_index == None {
+    if web_index.is_none() {
         web_index = Some("/html/home.html".to_string());
     }
-    if gen_args == None {
+    if gen_args.is_none() {
         gen_args = Some(None);
     }
-    if jar_name == None {
+    if jar_name.is_none() {
         jar_name = Some("minecraft_server.1.16.4.jar".to_string());
     }
-    if min_ram == None {
+    if min_ram.is_none() {
         min_ram = Some("1G".to_string());
     }
-    if max_ram == None {
+    if max_ram.is_none() {
         max_ram = Some("2G".to_string());
     }
-    if download_public == None {
+    if download_public.is_none() {
         download_public = Some(false);
     }
-    if web_log == None {
+    if web_log.is_none() {
         web_log = Some(false);
     }
-    if verbosity == None {
+    if verbosity.is_none() {
         verbosity = Some(Verbosity::None);
     }
+    if stop_timeout.is_none() {
+        stop_timeout = Some(60);
+    }
+    if countdown_warnings.is_none() {
+        countdown_warnings = Some(vec![300, 60, 30, 10, 5, 4, 3, 2, 1]);
+    }
+    if schedule.is_none() {
+        schedule = Some(file_schedule);
+    }
+    if backup_location.is_none() {
+        backup_location = Some("./backups".to_string());
+    }
+    if crash_limit.is_none() {
+        crash_limit = Some(5);
+    }
+    if crash_window.is_none() {
+        crash_window = Some(10);
+    }
+    if crash_backoff.is_none() {
+        crash_backoff = Some(5);
+    }
+    if list_interval.is_none() {
+        list_interval = Some(60);
+    }
+    if performance_interval.is_none() {
+        performance_interval = Some(60);
+    }
+    if rcon_port.is_none() {
+        rcon_port = Some(0);
+    }
+    if rcon_password.is_none() {
+        rcon_password = Some(String::new());
+    }
+    if query_responder.is_none() {
+        query_responder = Some(false);
+    }
+    if placeholder.is_none() {
+        placeholder = Some(false);
+    }
+    if placeholder_motd.is_none() {
+        placeholder_motd = Some("Restarting, back in {eta}".to_string());
+    }
+    if placeholder_message.is_none() {
+        placeholder_message = Some("The server is restarting, try again in {eta}".to_string());
+    }
+    if rcon_port != Some(0) && rcon_password.as_deref() == Some("") {
+        panic!("The monitor's RCON is enabled with rcon_port but no rcon_password was set");
+    }
 
     // Download and determine if the web dir exists
     if !Path::new("../public").exists() {
@@ -243,7 +470,7 @@ pub fn determine_config(
             // Create public directory
             match fs::create_dir("public") {
                 Ok(_) => {
-                    let mut config_curl = Command::new("curl").arg("-s").arg("https://raw.githubusercontent.com/nuhtan/minecraft_monitor/main/public/manifest.json").arg("-o").arg(format!("public/manifest.json")).spawn().expect("Error?");
+                    let mut config_curl = Command::new("curl").arg("-s").arg("https://raw.githubusercontent.com/nuhtan/minecraft_monitor/main/public/manifest.json").arg("-o").arg("public/manifest.json").spawn().expect("Error?");
                     config_curl.wait()?;
                     println!("Manifest file downloaded");
                     // Read manifest
@@ -254,7 +481,7 @@ pub fn determine_config(
                     // Parse manifest
                     let mut file_list = Vec::new();
                     let mut files = manifest[1..manifest.len() - 1].replace("\n    ", "");
-                    while files.len() > 0 {
+                    while !files.is_empty() {
                         let end = match files.find("\",\"") {
                             Some(ending) => ending,
                             None => match files.find("\"\n") {
@@ -271,13 +498,13 @@ pub fn determine_config(
                     let mut thread_list = Vec::new();
                     for file in file_list {
                         let mut ext = "";
-                        for (i, c) in file.chars().into_iter().rev().enumerate() {
+                        for (i, c) in file.chars().rev().enumerate() {
                             if c == '.' {
                                 ext = &file[file.len() - 1 - i..];
                                 break;
                             }
                         }
-                        if ext == "" {
+                        if ext.is_empty() {
                             panic!("File missing extension, found: {:?}", ext);
                         }
                         let folder = match ext {
@@ -312,19 +539,37 @@ pub fn determine_config(
     if !jar_check.exists() {
         panic!("All of your configuration is correct but the specified jar file was not found in the specified server folder, expected: {}", jar_check.display());
     }
+    // Without a configured flavor it is detected from the jar
+    let flavor = flavor.flatten().unwrap_or_else(|| detect_flavor(jar_check));
 
-    Ok((
-        address.unwrap(),
-        port.unwrap(),
-        web_index.unwrap(),
-        root_location.unwrap(),
-        jar_name.unwrap(),
-        gen_args.unwrap(),
-        min_ram.unwrap(),
-        max_ram.unwrap(),
-        web_log.unwrap(),
-        verbosity.unwrap(),
-    ))
+    Ok(Configuration {
+        address: address.unwrap(),
+        port: port.unwrap(),
+        web_index: web_index.unwrap(),
+        root_location: root_location.unwrap(),
+        jar_name: jar_name.unwrap(),
+        gen_args: gen_args.unwrap(),
+        min_ram: min_ram.unwrap(),
+        max_ram: max_ram.unwrap(),
+        web_log: web_log.unwrap(),
+        verbosity: verbosity.unwrap(),
+        stop_timeout: stop_timeout.unwrap(),
+        countdown_warnings: countdown_warnings.unwrap(),
+        schedule: verify_schedule(schedule.unwrap()),
+        backup_location: backup_location.unwrap(),
+        crash_limit: crash_limit.unwrap(),
+        crash_window: crash_window.unwrap(),
+        crash_backoff: crash_backoff.unwrap(),
+        list_interval: list_interval.unwrap(),
+        performance_interval: performance_interval.unwrap(),
+        rcon_port: rcon_port.unwrap(),
+        rcon_password: rcon_password.unwrap(),
+        query_responder: query_responder.unwrap(),
+        placeholder: placeholder.unwrap(),
+        placeholder_motd: placeholder_motd.unwrap(),
+        placeholder_message: placeholder_message.unwrap(),
+        flavor,
+    })
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -337,30 +582,28 @@ pub enum Verbosity {
 
 fn verify_address(arg: String) -> Ipv4Addr {
     match arg.parse::<Ipv4Addr>() {
-        Ok(addr) => return addr,
+        Ok(addr) => addr,
         Err(_) => panic!("Invalid ip address, found: {}", arg),
-    };
+    }
 }
 
 fn verify_port(arg: String) -> u16 {
     match arg.parse::<u16>() {
-        Ok(p) => return p,
+        Ok(p) => p,
         Err(_) => panic!("Invalid port, found {}", arg),
-    };
+    }
 }
 
 fn verify_location(arg: String) -> String {
     let path = Path::new(&arg);
-    if path.exists() {
-        return arg
-    } else {
+    if !path.exists() {
         println!(
             "Specified directory for the Minecraft server does not exist, now creating path: {}",
             path.display()
         );
         fs::create_dir_all(&arg).unwrap();
-        return arg
     }
+    arg
 }
 fn verify_jar(arg: String) -> String {
     let path = Path::new(&arg);
@@ -368,7 +611,7 @@ fn verify_jar(arg: String) -> String {
     match extension {
             Some(ext) => {
                 if ext == "jar" {
-                    return arg;
+                    arg
                 } else {
                     panic!("The file specified should be a .jar, found: {:?}", ext);
                 }
@@ -378,23 +621,23 @@ fn verify_jar(arg: String) -> String {
 }
 
 fn verify_general_args(arg: String) -> Option<String> {
-    return match arg.as_str() {
+    match arg.as_str() {
         "off" => None,
         _ => Some(arg),
-    };
+    }
 }
 
 fn verify_min_ram(arg: String) -> String {
     let data_size = arg.chars().last().unwrap();
     match data_size {
         'K' | 'M' | 'G' => {
-            return match arg[0..arg.len() - 1].parse::<u32>() {
+            match arg[0..arg.len() - 1].parse::<u32>() {
                 Ok(_) => arg,
                 Err(_) => panic!(
                     "Invalid number found for minimum allocated ram, found: {}",
                     &arg[0..arg.len() - 1]
                 ),
-            };
+            }
         }
         _ => panic!(
             "Invalid data size found for minimum allocated ram, found: {:?}",
@@ -407,46 +650,148 @@ fn verify_max_ram(arg: String) -> String {
     let data_size = arg.chars().last().unwrap();
     match data_size {
         'K' | 'M' | 'G' => {
-            return match arg[0..arg.len() - 1].parse::<u32>() {
+            match arg[0..arg.len() - 1].parse::<u32>() {
                 Ok(_) => arg,
                 Err(_) => panic!(
                     "Invalid number found for maximum alloram, found: {}",
                     &arg[0..arg.len() - 1]
                 ),
-            };
+            }
         }
         _ => panic!(
             "Invalid data size found for maximum allocated ram, found: {}",
             data_size
         ),
-    };
+    }
 }
 
 fn verify_web_log(arg: String) -> bool {
-    return match arg.as_str() {
+    match arg.as_str() {
         "true" => true,
         "false" => false,
         _ => panic!("Boolean not found for web log, found: {}", arg),
-    };
+    }
 }
 
 fn verify_verbosity(arg: String) -> Verbosity {
-    return match arg.as_str() {
+    match arg.as_str() {
         "none" => Verbosity::None,
         "mine" => Verbosity::Mine,
         "web" => Verbosity::Web,
         "mineweb" => Verbosity::MineWeb,
         _ => panic!("Invalid parameter for verbosity found, found: {}", arg),
-    };
+    }
 }
 
 fn verify_download_web(arg: String) -> bool {
-    return match arg.as_str() {
+    match arg.as_str() {
         "true" => true,
         "false" => false,
         _ => panic!(
             "Boolean not found for download web directory, found: {}",
             arg
         ),
-    };
+    }
+}
+
+fn verify_stop_timeout(arg: String) -> u64 {
+    match arg.parse::<u64>() {
+        Ok(seconds) => seconds,
+        Err(_) => panic!("Invalid number of seconds for the stop timeout, found: {}", arg),
+    }
+}
+
+fn verify_countdown_warnings(arg: String) -> Vec<u64> {
+    let mut warnings = Vec::new();
+    for warning in arg.split(',') {
+        match warning.trim().parse::<u64>() {
+            Ok(seconds) => warnings.push(seconds),
+            Err(_) => panic!(
+                "Invalid number of seconds for a countdown warning, found: {}",
+                warning
+            ),
+        }
+    }
+    warnings.sort_unstable_by(|a, b| b.cmp(a));
+    warnings.dedup();
+    warnings
+}
+
+fn verify_schedule(lines: Vec<String>) -> Vec<ScheduledTask> {
+    let mut tasks = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        match ScheduledTask::parse(index as u32 + 1, line) {
+            Ok(task) => tasks.push(task),
+            Err(error) => panic!("Invalid schedule, {}", error),
+        }
+    }
+    tasks
+}
+
+fn verify_crash_limit(arg: String) -> u32 {
+    match arg.parse::<u32>() {
+        Ok(limit) if limit > 0 => limit,
+        _ => panic!("Invalid number of crashes for the crash limit, found: {}", arg),
+    }
+}
+
+fn verify_crash_window(arg: String) -> u64 {
+    match arg.parse::<u64>() {
+        Ok(minutes) => minutes,
+        Err(_) => panic!("Invalid number of minutes for the crash window, found: {}", arg),
+    }
+}
+
+fn verify_crash_backoff(arg: String) -> u64 {
+    match arg.parse::<u64>() {
+        Ok(seconds) => seconds,
+        Err(_) => panic!("Invalid number of seconds for the crash backoff, found: {}", arg),
+    }
+}
+
+fn verify_list_interval(arg: String) -> u64 {
+    match arg.parse::<u64>() {
+        Ok(seconds) => seconds,
+        Err(_) => panic!("Invalid number of seconds for the list interval, found: {}", arg),
+    }
+}
+
+fn verify_performance_interval(arg: String) -> u64 {
+    match arg.parse::<u64>() {
+        Ok(seconds) => seconds,
+        Err(_) => panic!("Invalid number of seconds for the performance interval, found: {}", arg),
+    }
+}
+
+fn verify_rcon_port(arg: String) -> u16 {
+    match arg.parse::<u16>() {
+        Ok(port) => port,
+        Err(_) => panic!("Invalid port for the monitor's RCON, found: {}", arg),
+    }
+}
+
+fn verify_query_responder(arg: String) -> bool {
+    match arg.as_str() {
+        "true" => true,
+        "false" => false,
+        _ => panic!("Boolean not found for the query responder, found: {}", arg),
+    }
+}
+
+fn verify_placeholder(arg: String) -> bool {
+    match arg.as_str() {
+        "true" => true,
+        "false" => false,
+        _ => panic!("Boolean not found for the placeholder, found: {}", arg),
+    }
+}
+
+fn verify_flavor(arg: String) -> Option<Arc<dyn ServerFlavor>> {
+    match arg.as_str() {
+        "auto" => None,
+        _ => match flavor_named(&arg) {
+            Some(flavor) => Some(flavor),
+            None => panic!("Invalid server flavor, found: {}", arg),
+        },
+    }
 }
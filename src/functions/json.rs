@@ -0,0 +1,278 @@
+//! The little of JSON that the monitor reads, from server statuses and the server's list files, and escaping for the
+//! JSON that it writes.
+use std::{fmt, iter::Peekable, str::Chars};
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = String::new();
+        self.write(&mut text, None);
+        f.write_str(&text)
+    }
+}
+
+/// How deeply arrays and objects can be nested in JSON that is parsed.
+const MAX_JSON_DEPTH: usize = 128;
+
+/// Escapes text to be written between the quotes of a JSON string.
+pub fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A JSON value, only as much of JSON as is needed to read what servers send and to edit the server's lists.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut chars = text.chars().peekable();
+        let value = Json::parse_value(&mut chars, 0)?;
+        Json::skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Some(value),
+            Some(_) => None,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// Writes the value the way the server writes its files, each element and field on its own line.
+    pub fn pretty(&self) -> String {
+        let mut text = String::new();
+        self.write(&mut text, Some(0));
+        text
+    }
+
+    /// Writes the value to `text`, indented by `indent` levels or all on one line without it.
+    fn write(&self, text: &mut String, indent: Option<usize>) {
+        let (open, separator, close) = match indent {
+            Some(level) => (
+                format!("\n{}", "  ".repeat(level + 1)),
+                format!(",\n{}", "  ".repeat(level + 1)),
+                format!("\n{}", "  ".repeat(level)),
+            ),
+            None => (String::new(), ", ".to_string(), String::new()),
+        };
+        let inner = indent.map(|level| level + 1);
+        match self {
+            Json::Null => text.push_str("null"),
+            Json::Bool(value) => text.push_str(&value.to_string()),
+            Json::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                text.push_str(&(*number as i64).to_string())
+            }
+            Json::Number(number) => text.push_str(&number.to_string()),
+            Json::Text(value) => Json::write_string(text, value),
+            Json::Array(items) if items.is_empty() => text.push_str("[]"),
+            Json::Array(items) => {
+                text.push('[');
+                text.push_str(&open);
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        text.push_str(&separator);
+                    }
+                    item.write(text, inner);
+                }
+                text.push_str(&close);
+                text.push(']');
+            }
+            Json::Object(fields) if fields.is_empty() => text.push_str("{}"),
+            Json::Object(fields) => {
+                text.push('{');
+                text.push_str(&open);
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        text.push_str(&separator);
+                    }
+                    Json::write_string(text, name);
+                    text.push_str(": ");
+                    value.write(text, inner);
+                }
+                text.push_str(&close);
+                text.push('}');
+            }
+        }
+    }
+
+    fn write_string(text: &mut String, value: &str) {
+        text.push('"');
+        text.push_str(&json_escape(value));
+        text.push('"');
+    }
+
+    fn skip_whitespace(chars: &mut Peekable<Chars>) {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+    }
+
+    fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> Option<Json> {
+        // Text from other servers can nest deep enough to overflow the stack
+        if depth > MAX_JSON_DEPTH {
+            return None;
+        }
+        Json::skip_whitespace(chars);
+        match *chars.peek()? {
+            '{' => {
+                chars.next();
+                let mut fields = Vec::new();
+                Json::skip_whitespace(chars);
+                if chars.peek() == Some(&'}') {
+                    chars.next();
+                    return Some(Json::Object(fields));
+                }
+                loop {
+                    Json::skip_whitespace(chars);
+                    let name = match Json::parse_value(chars, depth + 1)? {
+                        Json::Text(name) => name,
+                        _ => return None,
+                    };
+                    Json::skip_whitespace(chars);
+                    if chars.next()? != ':' {
+                        return None;
+                    }
+                    fields.push((name, Json::parse_value(chars, depth + 1)?));
+                    Json::skip_whitespace(chars);
+                    match chars.next()? {
+                        ',' => continue,
+                        '}' => return Some(Json::Object(fields)),
+                        _ => return None,
+                    }
+                }
+            }
+            '[' => {
+                chars.next();
+                let mut values = Vec::new();
+                Json::skip_whitespace(chars);
+                if chars.peek() == Some(&']') {
+                    chars.next();
+                    return Some(Json::Array(values));
+                }
+                loop {
+                    values.push(Json::parse_value(chars, depth + 1)?);
+                    Json::skip_whitespace(chars);
+                    match chars.next()? {
+                        ',' => continue,
+                        ']' => return Some(Json::Array(values)),
+                        _ => return None,
+                    }
+                }
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => return Some(Json::Text(text)),
+                        '\\' => match chars.next()? {
+                            'n' => text.push('\n'),
+                            't' => text.push('\t'),
+                            'r' => text.push('\r'),
+                            'b' => text.push('\u{8}'),
+                            'f' => text.push('\u{c}'),
+                            'u' => {
+                                let mut code = Json::parse_hex(chars)?;
+                                // Characters outside the basic plane are written as a pair of surrogates
+                                if (0xD800..0xDC00).contains(&code) && chars.peek() == Some(&'\\') {
+                                    // Only take the next escape if it is the low half, anything else is left to be read
+                                    let mut ahead = chars.clone();
+                                    ahead.next();
+                                    if ahead.next() == Some('u') {
+                                        if let Some(low) = Json::parse_hex(&mut ahead).filter(|low| (0xDC00..0xE000).contains(low)) {
+                                            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                                            *chars = ahead;
+                                        }
+                                    }
+                                }
+                                text.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                            }
+                            escaped => text.push(escaped),
+                        },
+                        c => text.push(c),
+                    }
+                }
+            }
+            't' | 'f' | 'n' => {
+                let mut word = String::new();
+                while chars.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    word.push(chars.next()?);
+                }
+                match word.as_str() {
+                    "true" => Some(Json::Bool(true)),
+                    "false" => Some(Json::Bool(false)),
+                    "null" => Some(Json::Null),
+                    _ => None,
+                }
+            }
+            _ => {
+                let mut number = String::new();
+                while chars.peek().is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                    number.push(chars.next()?);
+                }
+                number.parse().ok().map(Json::Number)
+            }
+        }
+    }
+
+    fn parse_hex(chars: &mut Peekable<Chars>) -> Option<u32> {
+        let hex = chars.take(4).collect::<String>();
+        u32::from_str_radix(&hex, 16).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_escape() {
+        assert_eq!(Json::parse("{\"a\": [1, true, null, \"\\ud83d\\ude00\"]}").unwrap().get("a"), Some(&Json::Array(vec![
+            Json::Number(1.0),
+            Json::Bool(true),
+            Json::Null,
+            Json::Text("😀".to_string())
+        ])));
+        assert_eq!(Json::parse("{\"a\": 1,}"), None);
+        // A lone high surrogate does not swallow the characters after it
+        assert_eq!(Json::parse("\"\\ud83d\\n\""), Some(Json::Text("\u{FFFD}\n".to_string())));
+        assert_eq!(Json::parse("\"\\ud83dab\""), Some(Json::Text("\u{FFFD}ab".to_string())));
+        assert_eq!(Json::parse(&"[".repeat(2_000_000)), None);
+        assert_eq!(json_escape("a\"b\\c\td\u{1}"), "a\\\"b\\\\c\\td\\u0001");
+    }
+}
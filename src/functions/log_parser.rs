@@ -0,0 +1,343 @@
+//! Parsing of the Minecraft server's console output into its parts.
+//!
+//! Each server flavor prefixes its lines differently, a [`LogFormat`] recognises one of these prefixes. The
+//! [`LogParser`] tries each of its formats until one matches and then prefers that format for the following lines.
+
+/// The severity of a line of server output.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl LogLevel {
+    /// Reads a level as it is printed by log4j, or by older Bukkit servers that used java.util.logging.
+    pub fn parse(level: &str) -> Option<LogLevel> {
+        match level.trim() {
+            "TRACE" | "FINEST" | "FINER" => Some(LogLevel::Trace),
+            "DEBUG" | "FINE" | "CONFIG" => Some(LogLevel::Debug),
+            "INFO" => Some(LogLevel::Info),
+            "WARN" | "WARNING" => Some(LogLevel::Warn),
+            "ERROR" | "SEVERE" => Some(LogLevel::Error),
+            "FATAL" => Some(LogLevel::Fatal),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+            LogLevel::Fatal => "FATAL",
+        }
+    }
+}
+
+/// A single line of server output split into its parts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLine {
+    /// The timestamp as it was printed, usually `HH:MM:SS`.
+    pub time: Option<String>,
+    pub thread: Option<String>,
+    pub level: Option<LogLevel>,
+    /// The class or plugin that printed the line, only some formats include it.
+    pub logger: Option<String>,
+    /// The line without its prefix or line ending.
+    pub message: String,
+    /// Lines without a prefix, such as the rest of a stack trace, continue the line before them and share its parts.
+    pub continuation: bool,
+}
+
+impl LogLine {
+    fn new(time: &str, thread: Option<&str>, level: &str, logger: Option<&str>, message: &str) -> LogLine {
+        LogLine {
+            time: Some(time.to_string()),
+            thread: thread.map(|thread| thread.to_string()),
+            level: LogLevel::parse(level),
+            logger: logger.map(|logger| logger.to_string()),
+            message: message.to_string(),
+            continuation: false,
+        }
+    }
+}
+
+/// Recognises the prefix that one kind of server puts on its output.
+pub trait LogFormat: Send {
+    fn name(&self) -> &str;
+
+    /// Returns `None` if the line was not printed in this format.
+    fn parse(&self, line: &str) -> Option<LogLine>;
+}
+
+/// `[12:01:44] [Server thread/INFO]: message`
+pub struct VanillaFormat;
+
+impl LogFormat for VanillaFormat {
+    fn name(&self) -> &str {
+        "vanilla"
+    }
+
+    fn parse(&self, line: &str) -> Option<LogLine> {
+        let (time, rest) = bracketed(line)?;
+        let (source, rest) = bracketed(rest.strip_prefix(' ')?)?;
+        let message = rest.strip_prefix(": ")?;
+        let (thread, level) = source.rsplit_once('/')?;
+        if !is_clock(time) {
+            return None;
+        }
+        Some(LogLine::new(time, Some(thread), level, None, message))
+    }
+}
+
+/// `[12:01:44 INFO]: message`, used by Paper, Spigot and other Bukkit based servers.
+pub struct PaperFormat;
+
+impl LogFormat for PaperFormat {
+    fn name(&self) -> &str {
+        "paper"
+    }
+
+    fn parse(&self, line: &str) -> Option<LogLine> {
+        let (prefix, rest) = bracketed(line)?;
+        let message = rest.strip_prefix(": ")?;
+        let (time, level) = prefix.split_once(' ')?;
+        if !is_clock(time) {
+            return None;
+        }
+        Some(LogLine::new(time, None, level, None, message))
+    }
+}
+
+/// `[12:01:44] [Server thread/INFO] [minecraft/DedicatedServer]: message`
+///
+/// Newer versions of Forge also print the date and milliseconds, `[19Oct2026 12:01:44.123]`, and end the logger
+/// with a slash.
+pub struct ForgeFormat;
+
+impl LogFormat for ForgeFormat {
+    fn name(&self) -> &str {
+        "forge"
+    }
+
+    fn parse(&self, line: &str) -> Option<LogLine> {
+        let (time, rest) = bracketed(line)?;
+        let (source, rest) = bracketed(rest.strip_prefix(' ')?)?;
+        let (logger, rest) = bracketed(rest.strip_prefix(' ')?)?;
+        let message = rest.strip_prefix(": ")?;
+        let (thread, level) = source.rsplit_once('/')?;
+        let clock = time.rsplit(' ').next()?;
+        if !is_clock(clock.split('.').next()?) {
+            return None;
+        }
+        let logger = logger.strip_suffix('/').unwrap_or(logger);
+        Some(LogLine::new(time, Some(thread), level, Some(logger), message))
+    }
+}
+
+/// `12:01:44 [INFO] message`, used by BungeeCord and Waterfall.
+pub struct BungeeFormat;
+
+impl LogFormat for BungeeFormat {
+    fn name(&self) -> &str {
+        "bungeecord"
+    }
+
+    fn parse(&self, line: &str) -> Option<LogLine> {
+        let (time, rest) = line.split_once(' ')?;
+        let (level, message) = bracketed(rest)?;
+        if !is_clock(time) {
+            return None;
+        }
+        Some(LogLine::new(time, None, level, None, message.strip_prefix(' ').unwrap_or(message)))
+    }
+}
+
+/// Splits `[inner]rest` into its inner text and the rest of the line.
+fn bracketed(text: &str) -> Option<(&str, &str)> {
+    let text = text.strip_prefix('[')?;
+    let end = text.find(']')?;
+    Some((&text[..end], &text[end + 1..]))
+}
+
+/// Checks that the text is a `HH:MM:SS` time.
+fn is_clock(text: &str) -> bool {
+    text.len() == 8
+        && text.char_indices().all(|(i, c)| match i {
+            2 | 5 => c == ':',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+/// Splits lines of server output into [`LogLine`]s, detecting the format from the lines themselves.
+pub struct LogParser {
+    formats: Vec<Box<dyn LogFormat>>,
+    /// The format that matched the last line, it is tried first.
+    detected: Option<usize>,
+    previous: Option<LogLine>,
+}
+
+impl Default for LogParser {
+    fn default() -> Self {
+        LogParser::new(vec![Box::new(ForgeFormat), Box::new(VanillaFormat), Box::new(PaperFormat)])
+    }
+}
+
+impl LogParser {
+    pub fn new(formats: Vec<Box<dyn LogFormat>>) -> LogParser {
+        LogParser {
+            formats,
+            detected: None,
+            previous: None,
+        }
+    }
+
+    /// Adds a format that is tried before the existing ones.
+    pub fn with_format(mut self, format: Box<dyn LogFormat>) -> LogParser {
+        self.formats.insert(0, format);
+        self.detected = None;
+        self
+    }
+
+    /// The name of the format that the last recognised line was in.
+    pub fn format(&self) -> Option<&str> {
+        self.detected.map(|index| self.formats[index].name())
+    }
+
+    pub fn parse(&mut self, line: &str) -> LogLine {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let detected = self.detected.and_then(|index| Some((index, self.formats[index].parse(line)?)));
+        let parsed = detected.or_else(|| {
+            self.formats
+                .iter()
+                .enumerate()
+                .find_map(|(index, format)| Some((index, format.parse(line)?)))
+        });
+        let log_line = match parsed {
+            Some((index, log_line)) => {
+                self.detected = Some(index);
+                log_line
+            }
+            None => match &self.previous {
+                Some(previous) => LogLine {
+                    message: line.to_string(),
+                    continuation: true,
+                    ..previous.clone()
+                },
+                None => LogLine {
+                    time: None,
+                    thread: None,
+                    level: None,
+                    logger: None,
+                    message: line.to_string(),
+                    continuation: true,
+                },
+            },
+        };
+        self.previous = Some(log_line.clone());
+        log_line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(log: &str) -> (Vec<LogLine>, Option<String>) {
+        let mut parser = LogParser::default();
+        let lines = log.lines().map(|line| parser.parse(line)).collect();
+        (lines, parser.format().map(|format| format.to_string()))
+    }
+
+    #[test]
+    fn vanilla_log() {
+        let log = "\
+[12:01:40] [ServerMain/INFO]: Environment: authHost='https://authserver.mojang.com'
+[12:01:44] [Server thread/INFO]: Done (3.512s)! For help, type \"help\"
+[12:02:10] [Server thread/INFO]: Notch joined the game
+[12:02:15] [Server thread/WARN]: Can't keep up! Is the server overloaded? Running 2004ms or 40 ticks behind
+[12:02:20] [Server thread/INFO]: <Notch> [hello] there
+";
+        let (lines, format) = parse_all(log);
+        assert_eq!(format.as_deref(), Some("vanilla"));
+        assert_eq!(lines[1].message, "Done (3.512s)! For help, type \"help\"");
+        assert_eq!(lines[1].thread.as_deref(), Some("Server thread"));
+        assert_eq!(lines[2].time.as_deref(), Some("12:02:10"));
+        assert_eq!(lines[3].level, Some(LogLevel::Warn));
+        assert_eq!(lines[4].message, "<Notch> [hello] there");
+        assert!(lines.iter().all(|line| !line.continuation));
+    }
+
+    #[test]
+    fn paper_log() {
+        let log = "\
+[12:01:44 INFO]: Done (5.021s)! For help, type \"help\"
+[12:01:45 INFO]: [Essentials] Enabling Essentials v2.20.1
+[12:02:01 ERROR]: Could not pass event PlayerJoinEvent to Example v1.0
+java.lang.NullPointerException: null
+\tat com.example.Listener.onJoin(Listener.java:12) ~[?:?]
+\tat org.bukkit.plugin.SimplePluginManager.callEvent(SimplePluginManager.java:589) ~[paper-api.jar:?]
+[12:02:02 INFO]: Notch left the game
+";
+        let (lines, format) = parse_all(log);
+        assert_eq!(format.as_deref(), Some("paper"));
+        assert_eq!(lines[0].level, Some(LogLevel::Info));
+        assert_eq!(lines[0].thread, None);
+        assert_eq!(lines[1].message, "[Essentials] Enabling Essentials v2.20.1");
+        // The stack trace belongs to the error before it
+        assert!(lines[3].continuation && lines[5].continuation);
+        assert_eq!(lines[4].level, Some(LogLevel::Error));
+        assert_eq!(lines[4].time.as_deref(), Some("12:02:01"));
+        assert_eq!(lines[3].message, "java.lang.NullPointerException: null");
+        assert!(!lines[6].continuation);
+        assert_eq!(lines[6].message, "Notch left the game");
+    }
+
+    #[test]
+    fn forge_log() {
+        let log = "\
+[12:01:30] [main/INFO] [cpw.mods.modlauncher.Launcher/MODLAUNCHER]: ModLauncher running
+[12:01:44] [Server thread/INFO] [minecraft/DedicatedServer]: Done (12.402s)! For help, type \"help\"
+[19Oct2026 12:02:10.482] [Server thread/INFO] [net.minecraft.server.dedicated.DedicatedServer/]: Notch joined the game
+[19Oct2026 12:02:11.001] [Server thread/FATAL] [net.minecraftforge.common.ForgeMod/]: Preparing crash report
+";
+        let (lines, format) = parse_all(log);
+        assert_eq!(format.as_deref(), Some("forge"));
+        assert_eq!(lines[0].logger.as_deref(), Some("cpw.mods.modlauncher.Launcher/MODLAUNCHER"));
+        assert_eq!(lines[1].logger.as_deref(), Some("minecraft/DedicatedServer"));
+        assert_eq!(lines[1].message, "Done (12.402s)! For help, type \"help\"");
+        assert_eq!(lines[2].time.as_deref(), Some("19Oct2026 12:02:10.482"));
+        assert_eq!(lines[2].logger.as_deref(), Some("net.minecraft.server.dedicated.DedicatedServer"));
+        assert_eq!(lines[2].message, "Notch joined the game");
+        assert_eq!(lines[3].level, Some(LogLevel::Fatal));
+    }
+
+    #[test]
+    fn bungeecord_log() {
+        let mut parser = LogParser::new(vec![Box::new(BungeeFormat)]);
+        let line = parser.parse("12:01:44 [INFO] Listening on /0.0.0.0:25577");
+        assert_eq!(line.message, "Listening on /0.0.0.0:25577");
+        assert_eq!(line.level, Some(LogLevel::Info));
+        let line = parser.parse("12:01:50 [WARNING] [Notch] disconnected with: Timed out");
+        assert_eq!(line.level, Some(LogLevel::Warn));
+        assert_eq!(line.message, "[Notch] disconnected with: Timed out");
+    }
+
+    #[test]
+    fn unrecognised_lines() {
+        let mut parser = LogParser::default();
+        // Java prints some lines before the logger starts
+        let line = parser.parse("Starting net.minecraft.server.Main\n");
+        assert!(line.continuation);
+        assert_eq!(line.level, None);
+        assert_eq!(line.message, "Starting net.minecraft.server.Main");
+        assert_eq!(parser.parse("").message, "");
+        assert_eq!(parser.format(), None);
+    }
+}
@@ -1,7 +1,74 @@
 //!Module Description
-use std::sync::mpsc::Sender;
+use std::{
+    collections::VecDeque,
+    fs,
+    io::Write,
+    path::Path,
+    process::ExitStatus,
+    sync::{mpsc::Sender, Arc},
+    thread,
+    time::{Duration, Instant},
+};
 
-use super::shared_data;
+use super::configuration::Configuration;
+use super::log_parser::{BungeeFormat, LogLevel, LogLine, LogParser};
+use super::properties::ServerProperties;
+use super::scheduler::unix_time;
+use super::shared_data::{
+    self, Alert, ChatKind, ChatMessage, PerformanceSample, PerformanceSource, ConsoleCommand, GameEvent, GameEventKind, PlayerDrift, PlayerRecord, PlayerSession, CrashReport, GeneralState, MinecraftServerState, OutputStream, StartupRecord,
+    StartupStage,
+};
+
+/// The number of console lines that are kept with a crash report.
+const CRASH_REPORT_LINES: usize = 50;
+
+/// Where crash reports are written, relative to the server directory. Minecraft uses crash-reports for its own.
+const CRASH_REPORT_LOCATION: &str = "monitor-crash-reports";
+
+/// Where chat messages are saved, relative to the server directory.
+const CHAT_LOCATION: &str = "monitor-chat.log";
+
+/// The number of chat messages that are kept in memory, the file is trimmed to this when it grows to twice the size.
+const CHAT_HISTORY: usize = 5000;
+
+/// Where the players that have joined and their sessions are saved, relative to the server directory.
+const PLAYER_HISTORY_LOCATION: &str = "monitor-players.txt";
+
+/// The number of differences from the player list that are kept.
+const PLAYER_DRIFT_HISTORY: usize = 20;
+
+/// The number of different alerts that are kept.
+const ALERT_HISTORY: usize = 100;
+
+/// The number of lines of a stack trace that are kept with an alert.
+const ALERT_TRACE_LINES: usize = 50;
+
+/// The number of events that are kept for `/api/events`.
+const EVENT_HISTORY: usize = 1000;
+
+/// The number of sessions that are kept for each player.
+const SESSION_HISTORY: usize = 50;
+
+/// How often the player history is saved, at most this much playtime is lost if the monitor stops unexpectedly.
+pub const PLAYER_HISTORY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Where the time taken by each start is saved, relative to the server directory.
+const STARTUP_HISTORY_LOCATION: &str = "monitor-startup-times.txt";
+
+/// The number of previous starts that are kept in memory.
+const STARTUP_HISTORY: usize = 100;
+
+/// Where the measurements of the server's performance are saved, relative to the server directory.
+const PERFORMANCE_LOCATION: &str = "monitor-performance.txt";
+
+/// The number of performance measurements that are kept, a day of them at the default interval.
+const PERFORMANCE_HISTORY: usize = 1440;
+
+/// The ticks per second of a server that is keeping up.
+const FULL_TPS: f64 = 20.0;
+
+/// The longest that backoff will delay a restart after a crash.
+const MAX_CRASH_BACKOFF: u64 = 300;
 
 /// Function Description
 pub fn valid_username(name: &str) -> bool {
@@ -10,74 +77,1873 @@ pub fn valid_username(name: &str) -> bool {
             return false;
         }
     }
-    return true;
+    true
+}
+
+/// Strips the `[time] [thread/level]: ` prefix from a line of server output.
+pub fn line_message(line: &str) -> &str {
+    match line.find("]: ") {
+        Some(loc) => &line[loc + 3..],
+        None => line,
+    }
+}
+
+/// Removes the `§` formatting codes from text.
+pub fn strip_formatting(text: &str) -> String {
+    let mut plain = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Responses that are printed for any command that the server could not run.
+const COMMAND_ERRORS: [&str; 6] = [
+    "Unknown or incomplete command",
+    "Unknown command",
+    "Incorrect argument for command",
+    "No player was found",
+    "That player does not exist",
+    "<--[HERE]",
+];
+
+/// Returns the beginnings of the lines that the server prints in response to a command.
+///
+/// Only the first word of the command is used, commands that are not known return an empty slice.
+pub fn command_response_patterns(command: &str) -> &'static [&'static str] {
+    match command_word(command) {
+        "whitelist" => &[
+            "Added ",
+            "Removed ",
+            "Player is already whitelisted",
+            "Player is not whitelisted",
+            "Whitelist is ",
+            "There are ",
+            "Reloaded the whitelist",
+        ],
+        "op" => &["Made ", "Nothing changed. The player already is an operator"],
+        "deop" => &["Made ", "Nothing changed. The player is not an operator"],
+        "ban" => &["Banned ", "Nothing changed. The player is already banned"],
+        "ban-ip" => &["Banned IP ", "Nothing changed. That IP is already banned", "Invalid IP address"],
+        "pardon" => &["Unbanned ", "Nothing changed. The player isn't banned"],
+        "pardon-ip" => &["Unbanned IP ", "Nothing changed. That IP isn't banned", "Invalid IP address"],
+        "banlist" => &["There are ", "There is ", "There are no bans", " was banned by "],
+        "kick" => &["Kicked "],
+        "list" => &["There are "],
+        "say" => &["[Server] "],
+        "save-all" => &["Saving the game", "Saved the game"],
+        "save-on" | "save-off" => &["Automatic saving is now ", "Saving is already "],
+        "time" => &["Set the time to ", "The time is "],
+        "weather" => &["Set the weather to ", "Changing to "],
+        "difficulty" => &["The difficulty "],
+        "gamemode" | "defaultgamemode" => &["Set ", "The default game mode is now "],
+        "tp" | "teleport" => &["Teleported "],
+        "give" => &["Gave "],
+        "seed" => &["Seed: "],
+        _ => &[],
+    }
+}
+
+fn command_word(command: &str) -> &str {
+    command.trim_start_matches('/').split(' ').next().unwrap_or("")
+}
+
+/// `list`, `whitelist list` and `banlist` all answer with `There are ...`, this is the wording that only the given
+/// command's answer has.
+fn count_wording(command: &str) -> &'static str {
+    match command_word(command) {
+        "list" => "online",
+        "whitelist" => "whitelisted",
+        "banlist" => " ban",
+        _ => "",
+    }
+}
+
+/// Determines if a line of server output is a response to the given command.
+pub fn is_command_response(command: &str, line_content: &str) -> bool {
+    // Players can type anything into chat, only the console's own `say` is printed like a chat message
+    if let Some((kind, player, _)) = parse_chat(line_content) {
+        if kind != ChatKind::Say || player != "Server" {
+            return false;
+        }
+    }
+    COMMAND_ERRORS
+        .iter()
+        .any(|error| {
+            // The marker follows the part of the command that could not be read
+            if error.starts_with('<') {
+                line_content.ends_with(error)
+            } else {
+                line_content.starts_with(error)
+            }
+        })
+        || command_response_patterns(command)
+            .iter()
+            .any(|pattern| {
+                // Patterns with a leading space follow a player name
+                if pattern.starts_with(' ') {
+                    line_content.contains(pattern)
+                } else if pattern.starts_with("There ") {
+                    line_content.starts_with(pattern) && line_content.contains(count_wording(command))
+                } else {
+                    line_content.starts_with(pattern)
+                }
+            })
+}
+
+/// Console commands that only some kinds of server have.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Capability {
+    /// `tps`, ticks per second over the last 1, 5 and 15 minutes.
+    Tps,
+    /// `mspt`, milliseconds per tick.
+    Mspt,
+    /// `plugins`, the list of loaded plugins.
+    Plugins,
+    /// `forge tps`, ticks per second of each dimension.
+    ForgeTps,
+}
+
+impl Capability {
+    /// The console command that uses the capability.
+    pub fn command(&self) -> &str {
+        match self {
+            Capability::Tps => "tps",
+            Capability::Mspt => "mspt",
+            Capability::Plugins => "plugins",
+            Capability::ForgeTps => "forge tps",
+        }
+    }
+}
+
+/// The differences between the kinds of server that can be run, how their output is read, how they are launched
+/// and which commands they have.
+///
+/// The defaults are those of the vanilla server, other flavors only override what they change.
+pub trait ServerFlavor: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn capabilities(&self) -> &[Capability] {
+        &[]
+    }
+
+    fn has(&self, capability: Capability) -> bool {
+        self.capabilities().contains(&capability)
+    }
+
+    /// A parser for the server's output with the formats it prints in.
+    fn log_parser(&self) -> LogParser {
+        LogParser::default()
+    }
+
+    /// The arguments given to java when no generic args are configured.
+    fn launch_arguments(&self, config: &Configuration) -> Vec<String> {
+        jar_arguments(config, &config.jar_name, true)
+    }
+
+    /// The message that is printed once the server has finished starting.
+    fn is_started(&self, message: &str) -> bool {
+        message.starts_with("Done (") && message.contains(")! For help, type \"help\"")
+    }
+
+    /// The message that is printed once the server has been asked to stop.
+    fn is_stopping(&self, message: &str) -> bool {
+        message == "Closing Server"
+    }
+
+    fn is_eula_prompt(&self, message: &str) -> bool {
+        message == "You need to agree to the EULA in order to run the server. Go to eula.txt for more info."
+    }
+
+    /// Returns the name of the player that joined, if the message is a player joining.
+    fn player_joined<'a>(&self, message: &'a str) -> Option<&'a str> {
+        message.strip_suffix(" joined the game").filter(|name| valid_username(name))
+    }
+
+    /// Returns the name of the player that left, if the message is a player leaving.
+    fn player_left<'a>(&self, message: &'a str) -> Option<&'a str> {
+        message.strip_suffix(" left the game").filter(|name| valid_username(name))
+    }
+
+    /// The command that lists the players online, in the same format as vanilla.
+    fn list_command(&self) -> Option<&str> {
+        Some("list")
+    }
+
+    fn stop_command(&self) -> &str {
+        "stop"
+    }
+
+    /// Returns the kind, sender and text of a chat message.
+    fn chat_message<'a>(&self, message: &'a str) -> Option<(ChatKind, &'a str, &'a str)> {
+        parse_chat(message)
+    }
+
+    /// Returns details about a player that are printed around them joining and leaving.
+    fn player_detail<'a>(&self, message: &'a str) -> Option<PlayerDetail<'a>> {
+        parse_player_detail(message)
+    }
+}
+
+/// `-Xms<min> -Xmx<max> -XX:+UseG1GC -jar <jar>`, followed by `nogui` for servers that have one.
+fn jar_arguments(config: &Configuration, jar: &str, nogui: bool) -> Vec<String> {
+    let mut args = vec![
+        format!("-Xms{}", config.min_ram),
+        format!("-Xmx{}", config.max_ram),
+        "-XX:+UseG1GC".to_string(),
+        "-jar".to_string(),
+        jar.to_string(),
+    ];
+    if nogui {
+        args.push("nogui".to_string());
+    }
+    args
+}
+
+pub struct Vanilla;
+
+impl ServerFlavor for Vanilla {
+    fn name(&self) -> &str {
+        "vanilla"
+    }
+}
+
+/// Paper, Spigot, Purpur and other servers built on Bukkit.
+pub struct Paper;
+
+impl ServerFlavor for Paper {
+    fn name(&self) -> &str {
+        "paper"
+    }
+
+    fn capabilities(&self) -> &[Capability] {
+        &[Capability::Tps, Capability::Mspt, Capability::Plugins]
+    }
+}
+
+/// Forge and NeoForge, newer versions are launched with the argument files that the installer creates.
+pub struct Forge;
+
+impl ServerFlavor for Forge {
+    fn name(&self) -> &str {
+        "forge"
+    }
+
+    fn capabilities(&self) -> &[Capability] {
+        &[Capability::ForgeTps]
+    }
+
+    fn launch_arguments(&self, config: &Configuration) -> Vec<String> {
+        let args_file = if cfg!(windows) { "win_args.txt" } else { "unix_args.txt" };
+        let installs = ["libraries/net/minecraftforge/forge", "libraries/net/neoforged/neoforge"]
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join(args_file))
+            .filter(|path| path.exists())
+            .max();
+        match installs {
+            Some(path) => {
+                let mut args = vec![
+                    format!("-Xms{}", config.min_ram),
+                    format!("-Xmx{}", config.max_ram),
+                    "-XX:+UseG1GC".to_string(),
+                ];
+                if Path::new("user_jvm_args.txt").exists() {
+                    args.push("@user_jvm_args.txt".to_string());
+                }
+                args.push(format!("@{}", path.display()));
+                args.push("nogui".to_string());
+                args
+            }
+            None => jar_arguments(config, &config.jar_name, true),
+        }
+    }
+}
+
+pub struct Fabric;
+
+impl ServerFlavor for Fabric {
+    fn name(&self) -> &str {
+        "fabric"
+    }
+}
+
+/// BungeeCord and Waterfall proxies.
+pub struct BungeeCord;
+
+impl ServerFlavor for BungeeCord {
+    fn name(&self) -> &str {
+        "bungeecord"
+    }
+
+    fn log_parser(&self) -> LogParser {
+        LogParser::new(vec![Box::new(BungeeFormat)])
+    }
+
+    fn launch_arguments(&self, config: &Configuration) -> Vec<String> {
+        jar_arguments(config, &config.jar_name, false)
+    }
+
+    fn is_started(&self, message: &str) -> bool {
+        message.starts_with("Listening on ")
+    }
+
+    fn is_stopping(&self, message: &str) -> bool {
+        message.starts_with("Closing listener")
+    }
+
+    fn is_eula_prompt(&self, _message: &str) -> bool {
+        false
+    }
+
+    /// `[Notch] <-> ServerConnector [lobby] has connected`
+    fn player_joined<'a>(&self, message: &'a str) -> Option<&'a str> {
+        proxy_player(message, "] <-> ServerConnector [")
+    }
+
+    /// `[Notch] -> UpstreamBridge has disconnected`
+    fn player_left<'a>(&self, message: &'a str) -> Option<&'a str> {
+        proxy_player(message, "] -> UpstreamBridge has disconnected")
+    }
+
+    fn list_command(&self) -> Option<&str> {
+        None
+    }
+
+    fn stop_command(&self) -> &str {
+        "end"
+    }
+
+    /// Chat is logged by the servers behind the proxy.
+    fn chat_message<'a>(&self, _message: &'a str) -> Option<(ChatKind, &'a str, &'a str)> {
+        None
+    }
+
+    fn player_detail<'a>(&self, _message: &'a str) -> Option<PlayerDetail<'a>> {
+        None
+    }
+}
+
+pub struct Velocity;
+
+impl ServerFlavor for Velocity {
+    fn name(&self) -> &str {
+        "velocity"
+    }
+
+    fn launch_arguments(&self, config: &Configuration) -> Vec<String> {
+        jar_arguments(config, &config.jar_name, false)
+    }
+
+    fn is_started(&self, message: &str) -> bool {
+        message.starts_with("Done (")
+    }
+
+    fn is_stopping(&self, message: &str) -> bool {
+        message.starts_with("Shutting down the proxy")
+    }
+
+    fn is_eula_prompt(&self, _message: &str) -> bool {
+        false
+    }
+
+    /// `[connected player] Notch (/127.0.0.1:51234) has connected`
+    fn player_joined<'a>(&self, message: &'a str) -> Option<&'a str> {
+        message.strip_suffix(" has connected").and_then(velocity_player)
+    }
+
+    fn player_left<'a>(&self, message: &'a str) -> Option<&'a str> {
+        message.strip_suffix(" has disconnected").and_then(velocity_player)
+    }
+
+    fn list_command(&self) -> Option<&str> {
+        None
+    }
+
+    fn stop_command(&self) -> &str {
+        "shutdown"
+    }
+
+    /// Chat is logged by the servers behind the proxy.
+    fn chat_message<'a>(&self, _message: &'a str) -> Option<(ChatKind, &'a str, &'a str)> {
+        None
+    }
+
+    /// The address is printed in the line that the player connects with.
+    fn player_detail<'a>(&self, message: &'a str) -> Option<PlayerDetail<'a>> {
+        let rest = message.strip_suffix(" has connected")?.strip_prefix("[connected player] ")?;
+        let (name, address) = rest.split_once(" (/")?;
+        Some(PlayerDetail::Address(name, address.strip_suffix(')')?.rsplit_once(':')?.0))
+    }
+}
+
+/// Reads the name from a BungeeCord message that starts with `[name` and contains the marker after it.
+fn proxy_player<'a>(message: &'a str, marker: &str) -> Option<&'a str> {
+    let name = &message.strip_prefix('[')?[..message.find(marker)? - 1];
+    Some(name).filter(|name| valid_username(name))
+}
+
+fn velocity_player(message: &str) -> Option<&str> {
+    let rest = message.strip_prefix("[connected player] ")?;
+    let name = &rest[..rest.find(" (")?];
+    Some(name).filter(|name| valid_username(name))
+}
+
+/// Information about a player from the lines that the server prints around logins.
+#[derive(Debug, PartialEq)]
+pub enum PlayerDetail<'a> {
+    /// `UUID of player Notch is 069a79f4-44e9-4726-a5be-fca90e38aaf5`
+    Uuid(&'a str, &'a str),
+    /// `Notch[/127.0.0.1:51234] logged in with entity id 123 at (0.5, 64.0, 0.5)`
+    Address(&'a str, &'a str),
+    /// `Notch lost connection: Disconnected`
+    Disconnected(&'a str, &'a str),
+}
+
+fn parse_player_detail(message: &str) -> Option<PlayerDetail<'_>> {
+    if let Some(rest) = message.strip_prefix("UUID of player ") {
+        let (name, uuid) = rest.split_once(" is ")?;
+        return Some(PlayerDetail::Uuid(name, uuid)).filter(|_| valid_username(name));
+    }
+    if let Some((name, reason)) = message.split_once(" lost connection: ") {
+        return Some(PlayerDetail::Disconnected(name, reason)).filter(|_| valid_username(name));
+    }
+    let (name, rest) = message.split_once("[/")?;
+    let (address, rest) = rest.split_once(']')?;
+    if !rest.starts_with(" logged in with entity id") || !valid_username(name) {
+        return None;
+    }
+    Some(PlayerDetail::Address(name, address.rsplit_once(':').map_or(address, |(ip, _)| ip)))
+}
+
+/// The beginnings of vanilla death messages after the player's name, with a short name for the cause.
+///
+/// More specific messages come before those that they start with.
+const DEATH_MESSAGES: [(&str, &str); 60] = [
+    ("was shot by a skull from", "wither_skull"),
+    ("was shot by", "shot"),
+    ("was pummeled by", "pummeled"),
+    ("was pricked to death", "cactus"),
+    ("walked into a cactus whilst trying to escape", "cactus"),
+    ("drowned", "drowned"),
+    ("experienced kinetic energy", "kinetic_energy"),
+    ("blew up", "explosion"),
+    ("was blown up by", "explosion"),
+    ("was killed by even more magic", "magic"),
+    ("was killed by magic", "magic"),
+    ("was killed trying to hurt", "thorns"),
+    ("was killed while trying to hurt", "thorns"),
+    ("was killed by", "killed"),
+    ("hit the ground too hard", "fall"),
+    ("fell from a high place", "fall"),
+    ("fell off a ladder", "fall"),
+    ("fell off some vines", "fall"),
+    ("fell off some weeping vines", "fall"),
+    ("fell off some twisting vines", "fall"),
+    ("fell off scaffolding", "fall"),
+    ("fell while climbing", "fall"),
+    ("fell too far and was finished by", "fall"),
+    ("was doomed to fall", "fall"),
+    ("was impaled on a stalagmite", "stalagmite"),
+    ("was squashed by a falling anvil", "anvil"),
+    ("was squashed by a falling block", "falling_block"),
+    ("was skewered by a falling stalactite", "stalactite"),
+    ("went up in flames", "fire"),
+    ("walked into fire whilst fighting", "fire"),
+    ("burned to death", "fire"),
+    ("was burnt to a crisp whilst fighting", "fire"),
+    ("went off with a bang", "fireworks"),
+    ("tried to swim in lava", "lava"),
+    ("was struck by lightning", "lightning"),
+    ("discovered the floor was lava", "magma"),
+    ("walked into the danger zone due to", "magma"),
+    ("froze to death", "freeze"),
+    ("was frozen to death by", "freeze"),
+    ("was slain by", "slain"),
+    ("was fireballed by", "fireball"),
+    ("was stung to death", "sting"),
+    ("starved to death", "starve"),
+    ("suffocated in a wall", "suffocation"),
+    ("was squished too much", "cramming"),
+    ("was squashed by", "cramming"),
+    ("was poked to death by a sweet berry bush", "sweet_berry_bush"),
+    ("was impaled by", "trident"),
+    ("fell out of the world", "void"),
+    ("didn't want to live in the same world as", "void"),
+    ("left the confines of this world", "world_border"),
+    ("withered away", "wither"),
+    ("was obliterated by a sonically-charged shriek", "sonic_boom"),
+    ("was roasted in dragon's breath", "dragon_breath"),
+    ("was smashed by", "mace"),
+    ("was stomped by", "stomped"),
+    ("was spitballed by", "spit"),
+    ("was killed", "generic"),
+    ("died because of", "generic"),
+    ("died", "generic"),
+];
+
+/// Reads a vanilla death message into a death event.
+pub fn parse_death(message: &str) -> Option<(&str, GameEventKind)> {
+    let (player, rest) = message.split_once(' ')?;
+    if !valid_username(player) || player.is_empty() {
+        return None;
+    }
+    let (fragment, cause) = DEATH_MESSAGES.iter().find(|(fragment, _)| {
+        rest.strip_prefix(fragment)
+            .is_some_and(|after| after.is_empty() || after.starts_with(' '))
+    })?;
+    let after = rest[fragment.len()..].trim_start();
+    // Whoever was responsible follows the message, or 'whilst fighting' and the like
+    let responsible = [" by", " from", " as", " hurt", " of", " due to", " fighting", " escape"];
+    let killer = if responsible.iter().any(|ending| fragment.ends_with(ending)) {
+        Some(after)
+    } else {
+        [" whilst fighting ", " whilst trying to escape ", " by "]
+            .iter()
+            .find_map(|marker| after.split_once(marker).map(|(_, killer)| killer))
+            .or_else(|| after.strip_prefix("whilst fighting "))
+            .or_else(|| after.strip_prefix("whilst trying to escape "))
+            .or_else(|| after.strip_prefix("by "))
+    };
+    let killer = killer
+        .map(|killer| killer.split(" using ").next().unwrap_or(killer).trim())
+        .filter(|killer| !killer.is_empty())
+        .map(|killer| killer.to_string());
+    Some((
+        player,
+        GameEventKind::Death {
+            cause: cause.to_string(),
+            killer,
+            message: message.to_string(),
+        },
+    ))
+}
+
+/// Reads `Notch has made the advancement [Stone Age]` and the messages for challenges, goals and achievements.
+pub fn parse_advancement(message: &str) -> Option<(&str, GameEventKind)> {
+    let kinds = [
+        (" has made the advancement [", "advancement"),
+        (" has completed the challenge [", "challenge"),
+        (" has reached the goal [", "goal"),
+        (" has just earned the achievement [", "achievement"),
+    ];
+    kinds.iter().find_map(|(marker, kind)| {
+        let (player, title) = message.split_once(marker)?;
+        let title = title.strip_suffix(']')?;
+        if !valid_username(player) || player.is_empty() {
+            return None;
+        }
+        Some((
+            player,
+            GameEventKind::Advancement {
+                kind: kind.to_string(),
+                title: title.to_string(),
+            },
+        ))
+    })
+}
+
+/// Reads `<Player> message`, `* Player message` and `[Player] message` chat lines.
+pub fn parse_chat(message: &str) -> Option<(ChatKind, &str, &str)> {
+    // Servers since 1.19 mark messages that were not signed by the player
+    let message = message.strip_prefix("[Not Secure] ").unwrap_or(message);
+    let (kind, rest, end) = if let Some(rest) = message.strip_prefix('<') {
+        (ChatKind::Chat, rest, "> ")
+    } else if let Some(rest) = message.strip_prefix("* ") {
+        (ChatKind::Emote, rest, " ")
+    } else if let Some(rest) = message.strip_prefix('[') {
+        (ChatKind::Say, rest, "] ")
+    } else {
+        return None;
+    };
+    let (player, text) = rest.split_once(end)?;
+    if valid_username(player) && !player.is_empty() {
+        Some((kind, player, text))
+    } else {
+        None
+    }
+}
+
+/// Finds a flavor by the name used in the configuration.
+pub fn flavor_named(name: &str) -> Option<Arc<dyn ServerFlavor>> {
+    let flavor: Arc<dyn ServerFlavor> = match name {
+        "vanilla" => Arc::new(Vanilla),
+        "paper" | "spigot" | "purpur" | "bukkit" => Arc::new(Paper),
+        "forge" | "neoforge" => Arc::new(Forge),
+        "fabric" => Arc::new(Fabric),
+        "bungeecord" | "waterfall" => Arc::new(BungeeCord),
+        "velocity" => Arc::new(Velocity),
+        _ => return None,
+    };
+    Some(flavor)
+}
+
+/// Determines the flavor of a server jar from its name, or from the names of the classes within it.
+pub fn detect_flavor(jar: &Path) -> Arc<dyn ServerFlavor> {
+    let file_name = jar
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let by_name = [
+        ("velocity", "velocity"),
+        ("waterfall", "bungeecord"),
+        ("bungee", "bungeecord"),
+        ("neoforge", "forge"),
+        ("forge", "forge"),
+        ("fabric", "fabric"),
+        ("paper", "paper"),
+        ("purpur", "paper"),
+        ("spigot", "paper"),
+        ("bukkit", "paper"),
+    ]
+    .iter()
+    .find(|(pattern, _)| file_name.contains(pattern))
+    .map(|(_, flavor)| *flavor);
+    let name = by_name
+        .or_else(|| jar_directory(jar).and_then(|directory| flavor_from_entries(&directory)))
+        .unwrap_or("vanilla");
+    flavor_named(name).unwrap()
+}
+
+/// Matches the package names that each flavor ships in its jar.
+fn flavor_from_entries(directory: &[u8]) -> Option<&'static str> {
+    let contains = |package: &str| directory.windows(package.len()).any(|window| window == package.as_bytes());
+    [
+        ("com/velocitypowered/", "velocity"),
+        ("net/md_5/bungee/", "bungeecord"),
+        ("net/fabricmc/", "fabric"),
+        ("net/minecraftforge/", "forge"),
+        ("net/neoforged/", "forge"),
+        ("io/papermc/", "paper"),
+        ("org/bukkit/", "paper"),
+    ]
+    .iter()
+    .find(|(package, _)| contains(package))
+    .map(|(_, flavor)| *flavor)
+}
+
+/// Reads the central directory of a jar, which lists the name of every file within it uncompressed.
+fn jar_directory(jar: &Path) -> Option<Vec<u8>> {
+    let data = fs::read(jar).ok()?;
+    // The end of central directory record is 22 bytes followed by a comment of up to 65535 bytes
+    let search_from = data.len().saturating_sub(22 + 65535);
+    let end = (search_from..data.len().saturating_sub(21))
+        .rev()
+        .find(|&i| data[i..i + 4] == [0x50, 0x4b, 0x05, 0x06])?;
+    let size = u32::from_le_bytes([data[end + 12], data[end + 13], data[end + 14], data[end + 15]]) as usize;
+    let offset = u32::from_le_bytes([data[end + 16], data[end + 17], data[end + 18], data[end + 19]]) as usize;
+    data.get(offset..offset.checked_add(size)?).map(|directory| directory.to_vec())
+}
+
+/// Parses a line that the server wrote to stderr. The JVM prints crashes and uncaught exceptions there without a log
+/// prefix, so a line without one is an error unless it continues a stack trace.
+pub fn parse_stderr_line(parser: &mut LogParser, line: &str) -> LogLine {
+    let mut log_line = parser.parse(line);
+    let trace = log_line.message.starts_with([' ', '\t'])
+        || log_line.message.starts_with("Caused by: ")
+        || log_line.message.starts_with("...");
+    if log_line.continuation && !trace && !log_line.message.is_empty() {
+        log_line.level = Some(LogLevel::Error);
+        log_line.continuation = false;
+    }
+    log_line
 }
 
 // Server output reading
 pub fn server_output_scanning(
-    line_content: &str,
+    line: &LogLine,
     data: shared_data::ServerSharedData,
-    web_sender: &Sender<String>,
+    web_sender: &Sender<ConsoleCommand>,
+    flavor: &dyn ServerFlavor,
 ) {
-    if line_content.len() >= 13 {
-        // Somewhat arbitrary
-        if &line_content[0..10] == "There are " {
-            // list and latter should be removed and max should be modified.
-            let list = &line_content[10..];
-            let latter = &list[list.find(" ").unwrap() + 13..];
-            let max = latter[0..latter.find(" ").unwrap()].parse::<u32>().unwrap();
-            // Verify current players
-            // Set/Update max player count
-            let mut pc_max = data.max_player_count.lock().unwrap(); // FIXME try_lock?
-            *pc_max = max;
-        } else if &line_content[0..6] == "Done (" {
-            let exc = line_content.find(")!").unwrap();
-            if &line_content[exc..] == ")! For help, type \"help\"\n" {
-                let mut state = data.mcserver_state.lock().unwrap();
-                *state = shared_data::MinecraftServerState::Running;
-            }
-            web_sender.send("list".to_string()).unwrap();
-        } else if &line_content[..] == "Closing Server\n" {
-            let mut state = data.mcserver_state.lock().unwrap();
-            *state = shared_data::MinecraftServerState::Off;
-        } else if &line_content[..] == "You need to agree to the EULA in order to run the server. Go to eula.txt for more info.\n" {
+    record_alert(&data, line);
+    // Bukkit based servers print the names of a `list` on the lines after it, often without a prefix
+    if continue_player_list(&data, &line.message) {
+        return;
+    }
+    // The rest of a stack trace is never an event
+    if line.continuation {
+        return;
+    }
+    let line_content = line.message.as_str();
+    if *data.mcserver_state.lock().unwrap() == MinecraftServerState::Starting {
+        if let Some((stage, percent)) = startup_stage(line_content) {
+            data.startup.lock().unwrap().advance(stage, percent);
+        }
+    }
+    if let Some((kind, player, message)) = flavor.chat_message(line_content) {
+        // Plugins also prefix their messages with their name, only online players and the console can use say
+        let sender = kind != ChatKind::Say
+            || player == "Server"
+            || data.current_players.lock().unwrap().iter().any(|online| online == player);
+        if sender {
+            record_chat(&data, kind, player, message);
+            return;
+        }
+    }
+    if let Some(detail) = flavor.player_detail(line_content) {
+        record_player_detail(&data, detail);
+    }
+    if let Some((listed, max, names)) = parse_player_list(line_content) {
+        *data.max_player_count.lock().unwrap() = max; // FIXME try_lock?
+        match names {
+            Some(names) => reconcile_players(&data, listed, names),
+            None if listed == 0 => reconcile_players(&data, 0, Vec::new()),
+            None => *data.pending_list.lock().unwrap() = Some((listed, Vec::new())),
+        }
+    } else if flavor.is_started(line_content) {
+        record_startup(&data, line_content);
+        {
             let mut state = data.mcserver_state.lock().unwrap();
-            *state = shared_data::MinecraftServerState::Eula;
-        } else {
-            match line_content.find(" ") {
-                Some(loc) => {
-                    // Do length checks to avoid exceptions
-                    let name = &line_content[0..loc];
-                    // Player interaction
-                    if valid_username(name) {
-                        // Player joining
-                        if &line_content[loc + 1..line_content.len() - 1] == "joined the game" {
-                            let mut players_current = data.current_players.lock().unwrap(); // FIXME try_lock?
-                            if !players_current.contains(&name.to_string()) {
-                                players_current.push(name.to_string());
-                                let mut pc = data.current_player_count.lock().unwrap(); // FIXME try_lock?
-                                *pc += 1;
-                            }
-                        // Player leaving
-                        } else if &line_content[loc + 1..line_content.len() - 1] == "left the game"
-                        {
-                            let mut players_current = data.current_players.lock().unwrap(); // FIXME try_lock?
-                            if players_current.contains(&name.to_string()) {
-                                let loc = players_current
-                                    .iter()
-                                    .position(|look| name == look)
-                                    .unwrap();
-                                players_current.swap_remove(loc);
-                                let mut pc = data.current_player_count.lock().unwrap(); // FIXME try_lock?
-                                *pc -= 1;
-                            }
+            *state = shared_data::MinecraftServerState::Running;
+        }
+        // The server has read its properties by now, later edits only apply after a restart
+        *data.loaded_properties.lock().unwrap() = ServerProperties::load().ok();
+        if let Some(list) = flavor.list_command() {
+            web_sender.send(ConsoleCommand::new(list)).unwrap();
+        }
+    } else if flavor.is_stopping(line_content) {
+        let mut state = data.mcserver_state.lock().unwrap();
+        *state = shared_data::MinecraftServerState::Off;
+    } else if flavor.is_eula_prompt(line_content) {
+        let mut state = data.mcserver_state.lock().unwrap();
+        *state = shared_data::MinecraftServerState::Eula;
+    } else if let Some(name) = flavor.player_joined(line_content) {
+        let mut players_current = data.current_players.lock().unwrap(); // FIXME try_lock?
+        if !players_current.contains(&name.to_string()) {
+            players_current.push(name.to_string());
+            let mut pc = data.current_player_count.lock().unwrap(); // FIXME try_lock?
+            *pc = players_current.len() as u32;
+        }
+        record_join(&data, name);
+    } else if let Some(name) = flavor.player_left(line_content) {
+        let mut players_current = data.current_players.lock().unwrap(); // FIXME try_lock?
+        if let Some(loc) = players_current.iter().position(|look| name == look) {
+            players_current.swap_remove(loc);
+            let mut pc = data.current_player_count.lock().unwrap(); // FIXME try_lock?
+            *pc = players_current.len() as u32;
+        }
+        record_leave(&data, name, None);
+    } else if let Some((player, advancement)) = parse_advancement(line_content) {
+        record_advancement(&data, player, advancement);
+    } else if let Some((player, death)) = parse_death(line_content) {
+        // Mobs that are named with a name tag print death messages too
+        if data.current_players.lock().unwrap().iter().any(|online| online == player) {
+            record_death(&data, player, death);
+        }
+    } else if let Some((tps, mspt, source)) = parse_performance(line_content) {
+        record_performance(&data, tps, mspt, source);
+    }
+}
+
+/// Reads the response to `list`, `There are 2 of a max of 20 players online: Notch, jeb_`, into the number of
+/// players online, the max and the names.
+///
+/// Bukkit based servers print `There are 2 out of maximum 20 players online.` and the names on the following lines,
+/// for those no names are returned and the names are read with [`parse_list_group`].
+pub fn parse_player_list(message: &str) -> Option<(u32, u32, Option<Vec<&str>>)> {
+    let list = message.strip_prefix("There are ")?;
+    let (counts, names) = match list.split_once(" players online") {
+        Some((counts, names)) => (counts, names),
+        None => (list.strip_suffix(" players online")?, ""),
+    };
+    let mut numbers = counts.split([' ', '/']).filter_map(|number| number.parse::<u32>().ok());
+    let listed = numbers.next()?;
+    let max = numbers.next()?;
+    let names = names.strip_prefix(": ").or_else(|| names.strip_prefix(':')).map(|names| {
+        names
+            .split(", ")
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<&str>>()
+    });
+    Some((listed, max, names.filter(|names| names.len() as u32 == listed)))
+}
+
+/// Reads a line of names that follows a `list` on Bukkit based servers, such as `default: Notch, jeb_`. Names can be
+/// grouped by their permission group and can have prefixes such as `[AFK]`.
+pub fn parse_list_group(message: &str) -> Option<Vec<&str>> {
+    let names = message.split_once(": ").map_or(message, |(_, names)| names);
+    names
+        .split(", ")
+        .map(|name| {
+            let name = name.trim();
+            name.rsplit_once(']').map_or(name, |(_, name)| name).trim_start_matches('~')
+        })
+        .map(|name| Some(name).filter(|name| !name.is_empty() && valid_username(name)))
+        .collect()
+}
+
+/// Adds the names on a line after a `list` that did not include them, the players are corrected once every listed
+/// player has been named. Returns whether the line was part of the list.
+///
+/// If the names stop before all the listed players are named, only the number of players online is corrected.
+fn continue_player_list(data: &shared_data::ServerSharedData, message: &str) -> bool {
+    let mut pending = data.pending_list.lock().unwrap();
+    let names = match pending.as_mut() {
+        Some((_, names)) => names,
+        None => return false,
+    };
+    match parse_list_group(message) {
+        Some(group) => {
+            names.extend(group.iter().map(|name| name.to_string()));
+            if let Some((listed, names)) = pending.take_if(|(listed, names)| names.len() as u32 >= *listed) {
+                drop(pending);
+                reconcile_players(data, listed, names.iter().map(|name| name.as_str()).collect());
+            }
+            true
+        }
+        None => {
+            if let Some((listed, _)) = pending.take() {
+                *data.current_player_count.lock().unwrap() = listed;
+            }
+            false
+        }
+    }
+}
+
+/// Returns the players that are listed but not online, and those that are online but not listed.
+fn list_difference(online: &[String], listed: &[&str]) -> (Vec<String>, Vec<String>) {
+    let added = listed
+        .iter()
+        .filter(|name| !online.iter().any(|player| player == *name))
+        .map(|name| name.to_string())
+        .collect();
+    let removed = online
+        .iter()
+        .filter(|player| !listed.contains(&player.as_str()))
+        .cloned()
+        .collect();
+    (added, removed)
+}
+
+/// Corrects the players online from the server's `list`, any differences from the joins and leaves that were counted
+/// are reported and kept.
+fn reconcile_players(data: &shared_data::ServerSharedData, listed: u32, names: Vec<&str>) {
+    let (tracked, added, removed) = {
+        let mut players_current = data.current_players.lock().unwrap(); // FIXME try_lock?
+        let tracked = players_current.len() as u32;
+        let (added, removed) = list_difference(&players_current, &names);
+        *players_current = names.iter().map(|name| name.to_string()).collect();
+        *data.current_player_count.lock().unwrap() = listed;
+        (tracked, added, removed)
+    };
+    if tracked == listed && added.is_empty() && removed.is_empty() {
+        return;
+    }
+    for name in &added {
+        record_join(data, name);
+    }
+    for name in &removed {
+        record_leave(data, name, Some("Missing from the player list"));
+    }
+    println!(
+        "\x1b[0;33m[Players]:\x1b[0m {} players were counted online but {} were listed, added: [{}], removed: [{}]",
+        tracked,
+        listed,
+        added.join(", "),
+        removed.join(", ")
+    );
+    let mut drift = data.player_drift.lock().unwrap();
+    drift.push_back(PlayerDrift {
+        time: unix_time(),
+        tracked,
+        listed,
+        added,
+        removed,
+    });
+    while drift.len() > PLAYER_DRIFT_HISTORY {
+        drift.pop_front();
+    }
+}
+
+/// Recognises the milestones that are printed while the server starts, along with the percentage of the stage if given.
+pub fn startup_stage(message: &str) -> Option<(StartupStage, Option<u32>)> {
+    if let Some(percent) = message.strip_prefix("Preparing spawn area: ") {
+        return Some((StartupStage::PreparingSpawn, percent.trim_end_matches('%').parse::<u32>().ok()));
+    }
+    let stages = [
+        ("Loading libraries", StartupStage::LoadingLibraries),
+        ("Starting minecraft server version", StartupStage::LoadingProperties),
+        ("Loading properties", StartupStage::LoadingProperties),
+        ("Loading server plugin", StartupStage::LoadingPlugins),
+        ("Found new data pack", StartupStage::LoadingDatapacks),
+        ("Reloading ResourceManager", StartupStage::LoadingDatapacks),
+        ("Loaded ", StartupStage::LoadingDatapacks),
+        ("Preparing level", StartupStage::PreparingLevel),
+        ("Preparing start region", StartupStage::PreparingSpawn),
+    ];
+    stages
+        .iter()
+        .find(|(pattern, _)| {
+            // Paper and Forge prefix plugin and mod messages with their name
+            let message = match message.strip_prefix('[') {
+                Some(rest) => rest.split_once("] ").map_or(message, |(_, rest)| rest),
+                None => message,
+            };
+            match *pattern {
+                // Recipes and advancements are loaded from the data packs
+                "Loaded " => message.ends_with(" recipes") || message.ends_with(" advancements"),
+                _ => message.starts_with(pattern),
+            }
+        })
+        .map(|(_, stage)| (*stage, None))
+}
+
+/// Keeps warnings and errors as alerts, the lines without a prefix that follow one are added to its trace.
+fn record_alert(data: &shared_data::ServerSharedData, line: &LogLine) {
+    let mut alerts = data.alerts.lock().unwrap();
+    if line.continuation {
+        if let Some(alert) = alerts.iter_mut().find(|alert| alert.open) {
+            if alert.trace.len() < ALERT_TRACE_LINES {
+                alert.trace.push(line.message.clone());
+            }
+        }
+        return;
+    }
+    alerts.iter_mut().for_each(|alert| alert.open = false);
+    let level = match line.level {
+        Some(level) if level >= LogLevel::Warn => level,
+        _ => return,
+    };
+    if let Some((ms, ticks)) = parse_lag(&line.message) {
+        let mut lag = data.lag.lock().unwrap();
+        lag.count += 1;
+        lag.skipped_ms += ms;
+        lag.skipped_ticks += ticks;
+        lag.max_ms = lag.max_ms.max(ms);
+        lag.last = Some(unix_time());
+    }
+    let now = unix_time();
+    let key = alert_key(&line.message);
+    match alerts.iter_mut().find(|alert| alert.level == level && alert.key == key) {
+        Some(alert) => {
+            alert.count += 1;
+            alert.last_seen = now;
+            alert.trace.clear();
+            alert.open = true;
+        }
+        None => {
+            let id = alerts.iter().map(|alert| alert.id).max().unwrap_or(0) + 1;
+            alerts.push(Alert {
+                id,
+                level,
+                message: line.message.clone(),
+                key,
+                thread: line.thread.clone(),
+                logger: line.logger.clone(),
+                trace: Vec::new(),
+                count: 1,
+                first_seen: now,
+                last_seen: now,
+                open: true,
+            });
+            if alerts.len() > ALERT_HISTORY {
+                // The alert that has gone the longest without happening again makes room
+                if let Some(oldest) = (0..alerts.len()).min_by_key(|&index| alerts[index].last_seen) {
+                    alerts.remove(oldest);
+                }
+            }
+        }
+    }
+}
+
+/// Removes the numbers from a message so that repeats with different figures are grouped together.
+fn alert_key(message: &str) -> String {
+    let mut key = String::new();
+    for c in message.chars() {
+        if !c.is_ascii_digit() {
+            key.push(c);
+        } else if !key.ends_with('#') {
+            key.push('#');
+        }
+    }
+    key
+}
+
+/// Reads the milliseconds and ticks from `Can't keep up! Is the server overloaded? Running 2004ms or 40 ticks behind`.
+pub fn parse_lag(message: &str) -> Option<(u64, u64)> {
+    let behind = message
+        .strip_prefix("Can't keep up! Is the server overloaded? Running ")?
+        .strip_suffix(" ticks behind")?;
+    let (ms, ticks) = behind.split_once("ms or ")?;
+    Some((ms.parse().ok()?, ticks.parse().ok()?))
+}
+
+/// Keeps an event for `/api/events`.
+fn record_event(data: &shared_data::ServerSharedData, player: &str, kind: GameEventKind) {
+    let mut events = data.events.lock().unwrap();
+    let id = events.back().map_or(1, |event| event.id + 1);
+    events.push_back(GameEvent {
+        id,
+        time: unix_time(),
+        player: player.to_string(),
+        kind,
+    });
+    while events.len() > EVENT_HISTORY {
+        events.pop_front();
+    }
+}
+
+fn record_death(data: &shared_data::ServerSharedData, player: &str, kind: GameEventKind) {
+    player_record(&mut data.player_history.lock().unwrap(), player).deaths += 1;
+    player_history_changed(data);
+    record_event(data, player, kind);
+}
+
+fn record_advancement(data: &shared_data::ServerSharedData, player: &str, kind: GameEventKind) {
+    if let GameEventKind::Advancement { title, .. } = &kind {
+        let added = {
+            let mut players = data.player_history.lock().unwrap();
+            let record = player_record(&mut players, player);
+            !record.advancements.contains(title) && {
+                record.advancements.push(title.clone());
+                true
+            }
+        };
+        if added {
+            player_history_changed(data);
+        }
+    }
+    record_event(data, player, kind);
+}
+
+/// Finds the record of a player, creating it if they are new.
+fn player_record<'a>(players: &'a mut Vec<PlayerRecord>, name: &str) -> &'a mut PlayerRecord {
+    match players.iter().position(|player| player.name == name) {
+        Some(index) => &mut players[index],
+        None => {
+            players.push(PlayerRecord::new(name));
+            players.last_mut().unwrap()
+        }
+    }
+}
+
+fn record_player_detail(data: &shared_data::ServerSharedData, detail: PlayerDetail) {
+    let mut players = data.player_history.lock().unwrap();
+    match detail {
+        PlayerDetail::Uuid(name, uuid) => player_record(&mut players, name).uuid = Some(uuid.to_string()),
+        PlayerDetail::Address(name, ip) => player_record(&mut players, name).ip = Some(ip.to_string()),
+        PlayerDetail::Disconnected(name, reason) => {
+            let player = player_record(&mut players, name);
+            if let Some(session) = player.sessions.back_mut().filter(|session| session.left.is_none()) {
+                session.reason = Some(reason.to_string());
+            }
+        }
+    }
+    drop(players);
+    player_history_changed(data);
+}
+
+fn record_join(data: &shared_data::ServerSharedData, name: &str) {
+    let now = unix_time();
+    let mut players = data.player_history.lock().unwrap();
+    let player = player_record(&mut players, name);
+    if player.online() {
+        return;
+    }
+    player.sessions.push_back(PlayerSession {
+        joined: now,
+        left: None,
+        reason: None,
+        ip: player.ip.clone(),
+    });
+    while player.sessions.len() > SESSION_HISTORY {
+        player.sessions.pop_front();
+    }
+    drop(players);
+    player_history_changed(data);
+    record_event(data, name, GameEventKind::Join);
+}
+
+fn record_leave(data: &shared_data::ServerSharedData, name: &str, reason: Option<&str>) {
+    let ended = end_session(player_record(&mut data.player_history.lock().unwrap(), name), unix_time(), reason);
+    if ended {
+        player_history_changed(data);
+        record_event(data, name, GameEventKind::Leave);
+    }
+}
+
+/// Returns false if the player was not online.
+fn end_session(player: &mut PlayerRecord, now: u64, reason: Option<&str>) -> bool {
+    match player.sessions.back_mut().filter(|session| session.left.is_none()) {
+        Some(session) => {
+            session.left = Some(now.max(session.joined));
+            if session.reason.is_none() {
+                session.reason = reason.map(|reason| reason.to_string());
+            }
+            player.playtime += now.saturating_sub(session.joined);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Ends the session of every player that is still online, used once the server has stopped.
+pub fn end_player_sessions(data: &shared_data::ServerSharedData, reason: &str) {
+    let now = unix_time();
+    let ended = data
+        .player_history
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .filter_map(|player| match end_session(player, now, Some(reason)) {
+            true => Some(player.name.clone()),
+            false => None,
+        })
+        .collect::<Vec<String>>();
+    if !ended.is_empty() {
+        player_history_changed(data);
+    }
+    // The server has stopped, so there won't be anything else to save for a while
+    save_player_history(data, false);
+    for name in ended {
+        record_event(data, &name, GameEventKind::Leave);
+    }
+    data.current_players.lock().unwrap().clear();
+    *data.current_player_count.lock().unwrap() = 0;
+}
+
+/// Marks the player history as needing to be saved, it is written out every `PLAYER_HISTORY_INTERVAL`.
+fn player_history_changed(data: &shared_data::ServerSharedData) {
+    *data.player_history_changed.lock().unwrap() = true;
+}
+
+/// Saves every player if anything has changed, or to update the time that online players were last seen when
+/// `checkpoint` is set.
+///
+/// The file starts with a `saved` line, each player is then a `player` line followed by an `advancement` line for each
+/// advancement and a `session` line for each session, separated by tabs.
+pub fn save_player_history(data: &shared_data::ServerSharedData, checkpoint: bool) {
+    // Held while writing so that two saves can't overwrite each other, but the players are only held to copy them
+    let mut changed = data.player_history_changed.lock().unwrap();
+    let players = {
+        let players = data.player_history.lock().unwrap();
+        if !(*changed || (checkpoint && players.iter().any(PlayerRecord::online))) {
+            return;
+        }
+        players.clone()
+    };
+    *changed = false;
+    let optional = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    let mut contents = format!("saved\t{}\n", unix_time());
+    for player in &players {
+        contents.push_str(&format!(
+            "player\t{}\t{}\t{}\t{}\t{}\n",
+            player.name,
+            optional(&player.uuid),
+            optional(&player.ip),
+            player.playtime,
+            player.deaths
+        ));
+        for advancement in &player.advancements {
+            contents.push_str(&format!("advancement\t{}\n", advancement));
+        }
+        for session in &player.sessions {
+            contents.push_str(&format!(
+                "session\t{}\t{}\t{}\t{}\n",
+                session.joined,
+                session.left.map_or("-".to_string(), |left| left.to_string()),
+                optional(&session.ip),
+                optional(&session.reason.as_ref().map(|reason| reason.replace('\t', " ")))
+            ));
+        }
+    }
+    // Written to a temporary file first so that the history is never left half written
+    let temporary = format!("{}.tmp", PLAYER_HISTORY_LOCATION);
+    if fs::write(&temporary, contents)
+        .and_then(|_| fs::rename(&temporary, PLAYER_HISTORY_LOCATION))
+        .is_err()
+    {
+        println!("Failed to save the player history to {}", PLAYER_HISTORY_LOCATION);
+    }
+}
+
+/// Reads the players that were saved by the monitor, lines that can not be read are skipped.
+///
+/// Sessions that were still open when the monitor stopped are ended at the time the history was last saved, as the
+/// time the player left is not known.
+pub fn load_player_history() -> Vec<PlayerRecord> {
+    let contents = fs::read_to_string(PLAYER_HISTORY_LOCATION).unwrap_or_default();
+    let optional = |value: &str| match value {
+        "-" => None,
+        _ => Some(value.to_string()),
+    };
+    let mut players: Vec<PlayerRecord> = Vec::new();
+    let mut saved = None;
+    for line in contents.lines() {
+        let parts = line.split('\t').collect::<Vec<&str>>();
+        match parts.as_slice() {
+            ["saved", time] => saved = time.parse::<u64>().ok(),
+            ["player", name, uuid, ip, playtime, counters @ ..] => {
+                let mut player = PlayerRecord::new(name);
+                player.uuid = optional(uuid);
+                player.ip = optional(ip);
+                player.playtime = playtime.parse().unwrap_or(0);
+                player.deaths = counters.first().and_then(|deaths| deaths.parse().ok()).unwrap_or(0);
+                players.push(player);
+            }
+            ["advancement", title] => {
+                if let Some(player) = players.last_mut() {
+                    player.advancements.push(title.to_string());
+                }
+            }
+            ["session", joined, left, ip, reason] => {
+                if let (Some(player), Ok(joined)) = (players.last_mut(), joined.parse::<u64>()) {
+                    let ended = match left.parse::<u64>() {
+                        Ok(ended) => ended,
+                        Err(_) => {
+                            // Playtime is only added once a session ends
+                            let ended = saved.unwrap_or(joined).max(joined);
+                            player.playtime += ended - joined;
+                            ended
                         }
-                    }
+                    };
+                    player.sessions.push_back(PlayerSession {
+                        joined,
+                        left: Some(ended),
+                        ip: optional(ip),
+                        reason: optional(reason).or_else(|| match *left {
+                            "-" => Some("Unknown, the monitor stopped".to_string()),
+                            _ => None,
+                        }),
+                    });
                 }
-                None => {
-                    // There is no space and thus no way to match any of the possible
+            }
+            _ => {}
+        }
+    }
+    players
+}
+
+/// Keeps a chat message and saves it to disk.
+fn record_chat(data: &shared_data::ServerSharedData, kind: ChatKind, player: &str, message: &str) {
+    let chat = ChatMessage {
+        time: unix_time(),
+        player: player.to_string(),
+        message: message.to_string(),
+        kind,
+    };
+    let saved = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(CHAT_LOCATION)
+        .and_then(|mut file| file.write_all(chat_line(&chat).as_bytes()));
+    if saved.is_err() {
+        println!("Failed to save a chat message to {}", CHAT_LOCATION);
+    }
+    let mut history = data.chat.lock().unwrap();
+    history.push_back(chat);
+    while history.len() > CHAT_HISTORY {
+        history.pop_front();
+    }
+}
+
+/// `time kind player message` separated by tabs, which can't be typed in chat.
+fn chat_line(chat: &ChatMessage) -> String {
+    format!("{}\t{}\t{}\t{}\n", chat.time, chat.kind.name(), chat.player, chat.message.replace('\t', " "))
+}
+
+/// Reads the chat messages that were saved by the monitor, lines that can not be read are skipped.
+pub fn load_chat_history() -> VecDeque<ChatMessage> {
+    let contents = fs::read_to_string(CHAT_LOCATION).unwrap_or_default();
+    let lines = contents.lines().count();
+    let history = contents
+        .lines()
+        .skip(lines.saturating_sub(CHAT_HISTORY))
+        .filter_map(|line| {
+            let mut parts = line.splitn(4, '\t');
+            Some(ChatMessage {
+                time: parts.next()?.parse().ok()?,
+                kind: ChatKind::from_name(parts.next()?)?,
+                player: parts.next()?.to_string(),
+                message: parts.next()?.to_string(),
+            })
+        })
+        .collect::<VecDeque<ChatMessage>>();
+    if lines > CHAT_HISTORY * 2 {
+        let trimmed = history.iter().map(chat_line).collect::<String>();
+        if fs::write(CHAT_LOCATION, trimmed).is_err() {
+            println!("Failed to trim the saved chat in {}", CHAT_LOCATION);
+        }
+    }
+    history
+}
+
+/// Reads the seconds from `Done (12.345s)! For help, type "help"`.
+fn reported_startup(message: &str) -> Option<f64> {
+    let seconds = message.strip_prefix("Done (")?;
+    seconds[..seconds.find("s)")?].parse::<f64>().ok()
+}
+
+/// Marks the start as complete and saves how long it took.
+fn record_startup(data: &shared_data::ServerSharedData, message: &str) {
+    let record = {
+        let mut startup = data.startup.lock().unwrap();
+        if startup.duration.is_some() {
+            return;
+        }
+        let duration = startup.started.elapsed().as_secs_f64();
+        startup.advance(StartupStage::Done, None);
+        startup.duration = Some(duration);
+        StartupRecord {
+            time: unix_time(),
+            duration,
+            reported: reported_startup(message),
+        }
+    };
+    let line = format!(
+        "{} {:.3} {}\n",
+        record.time,
+        record.duration,
+        record.reported.map_or("-".to_string(), |reported| reported.to_string())
+    );
+    let saved = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(STARTUP_HISTORY_LOCATION)
+        .and_then(|mut file| file.write_all(line.as_bytes()));
+    if saved.is_err() {
+        println!("Failed to save the startup time to {}", STARTUP_HISTORY_LOCATION);
+    }
+    let mut history = data.startup_history.lock().unwrap();
+    history.push(record);
+    if history.len() > STARTUP_HISTORY {
+        history.remove(0);
+    }
+}
+
+/// Reads the times of previous starts that were saved by the monitor, lines that can not be read are skipped.
+pub fn load_startup_history() -> Vec<StartupRecord> {
+    let contents = fs::read_to_string(STARTUP_HISTORY_LOCATION).unwrap_or_default();
+    let mut history = contents
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split(' ');
+            Some(StartupRecord {
+                time: parts.next()?.parse().ok()?,
+                duration: parts.next()?.parse().ok()?,
+                reported: parts.next().and_then(|reported| reported.parse().ok()),
+            })
+        })
+        .collect::<Vec<StartupRecord>>();
+    let excess = history.len().saturating_sub(STARTUP_HISTORY);
+    history.drain(..excess);
+    history
+}
+
+/// Reads the responses to the commands that measure performance into the ticks per second and milliseconds per tick:
+///
+/// * `TPS from last 1m, 5m, 15m: 19.8, 20.0, 20.0` and `◴ 12.3/8.1/40.2, ...` from Paper's `tps` and `mspt`
+/// * `Overall : Mean tick time: 1.234 ms. Mean TPS: 20.000` or `Overall: 20.000 TPS (1.234 ms/tick)` from `forge tps`
+/// * `Stopped debug profiling after 10.00 seconds and 200 ticks (20.00 ticks per second)` from `debug stop`
+pub fn parse_performance(message: &str) -> Option<(Option<f64>, Option<f64>, PerformanceSource)> {
+    // Paper colours these, the codes are left in when the console can not show them
+    let plain = strip_formatting(message);
+    let number = |text: &str| text.trim().trim_start_matches('*').parse::<f64>().ok();
+    if let Some(tps) = plain.strip_prefix("TPS from last 1m, 5m, 15m: ") {
+        Some((Some(number(tps.split(',').next()?)?), None, PerformanceSource::Paper))
+    } else if let Some(times) = mspt_times(&plain) {
+        Some((None, Some(number(times.split('/').next()?)?), PerformanceSource::Paper))
+    } else if let Some(overall) = plain.strip_prefix("Overall") {
+        let overall = overall.trim_start().strip_prefix(':')?;
+        if let Some(mean) = overall.trim().strip_prefix("Mean tick time: ") {
+            let (mspt, tps) = mean.split_once(" ms. Mean TPS: ")?;
+            Some((Some(number(tps)?), Some(number(mspt)?), PerformanceSource::Forge))
+        } else {
+            let (tps, mspt) = overall.split_once(" TPS (")?;
+            Some((Some(number(tps)?), Some(number(mspt.strip_suffix(" ms/tick)")?)?), PerformanceSource::Forge))
+        }
+    } else if plain.starts_with("Stopped ") && plain.contains(" profiling after ") {
+        let tps = &plain[plain.rfind(" (")? + 2..];
+        Some((Some(number(&tps[..tps.find(' ')?])?), None, PerformanceSource::Profiler))
+    } else {
+        None
+    }
+}
+
+/// Finds the times in `◴ 12.3/8.1/40.2, 11.9/7.5/40.2, 12.0/6.2/52.8`, the clock is printed as `?` by consoles
+/// that are not using UTF-8 so anything before the first space is allowed.
+fn mspt_times(message: &str) -> Option<&str> {
+    let (clock, times) = message.split_once(' ')?;
+    let groups = times.split(", ").collect::<Vec<&str>>();
+    let valid = !clock.contains(|c: char| c.is_ascii_digit())
+        && groups.len() == 3
+        && groups.iter().all(|group| {
+            group.split('/').count() == 3 && group.split('/').all(|time| time.parse::<f64>().is_ok())
+        });
+    if valid {
+        Some(times)
+    } else {
+        None
+    }
+}
+
+/// Works out the ticks per second over `seconds` from the ticks that the server skipped to catch up.
+pub fn lag_tps(skipped_ticks: u64, seconds: f64) -> f64 {
+    if seconds <= 0.0 {
+        return FULL_TPS;
+    }
+    (FULL_TPS - skipped_ticks as f64 / seconds).max(0.0)
+}
+
+/// Keeps a performance measurement and saves the history.
+///
+/// Paper prints the milliseconds per tick separately, these are added to the ticks per second that came just before.
+pub fn record_performance(
+    data: &shared_data::ServerSharedData,
+    tps: Option<f64>,
+    mspt: Option<f64>,
+    source: PerformanceSource,
+) {
+    let now = unix_time();
+    let mut performance = data.performance.lock().unwrap();
+    match performance.back_mut() {
+        Some(last) if tps.is_none() && last.mspt.is_none() && last.source == source && now.saturating_sub(last.time) <= 10 => {
+            last.mspt = mspt;
+        }
+        _ => {
+            performance.push_back(PerformanceSample { time: now, tps, mspt, source });
+            if performance.len() > PERFORMANCE_HISTORY {
+                performance.pop_front();
+            }
+        }
+    }
+    let optional = |value: Option<f64>| value.map_or("-".to_string(), |value| value.to_string());
+    let contents = performance
+        .iter()
+        .map(|sample| format!("{} {} {} {}\n", sample.time, optional(sample.tps), optional(sample.mspt), sample.source.name()))
+        .collect::<String>();
+    let temporary = format!("{}.tmp", PERFORMANCE_LOCATION);
+    if fs::write(&temporary, contents)
+        .and_then(|_| fs::rename(&temporary, PERFORMANCE_LOCATION))
+        .is_err()
+    {
+        println!("Failed to save the performance history to {}", PERFORMANCE_LOCATION);
+    }
+}
+
+/// Reads the performance measurements that were saved by the monitor, lines that can not be read are skipped.
+pub fn load_performance_history() -> VecDeque<PerformanceSample> {
+    let contents = fs::read_to_string(PERFORMANCE_LOCATION).unwrap_or_default();
+    let mut history = contents
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split(' ');
+            Some(PerformanceSample {
+                time: parts.next()?.parse().ok()?,
+                tps: parts.next()?.parse().ok(),
+                mspt: parts.next()?.parse().ok(),
+                source: PerformanceSource::from_name(parts.next()?)?,
+            })
+        })
+        .collect::<VecDeque<PerformanceSample>>();
+    let excess = history.len().saturating_sub(PERFORMANCE_HISTORY);
+    history.drain(..excess);
+    history
+}
+
+/// Handles the Minecraft server closing its output, which only happens once the process is exiting.
+///
+/// Exits that were not asked for by a shutdown, restart or the EULA are recorded as crashes and the server is
+/// restarted. A server that prints `Closing Server` and exits successfully was stopped from the console and is not a crash.
+pub fn handle_server_exit(data: &shared_data::ServerSharedData) {
+    let status = wait_for_child(data);
+    let mut mc_state = data.mcserver_state.lock().unwrap();
+    let mut gen_state = data.gen_state.lock().unwrap();
+    if *gen_state != GeneralState::Running || *mc_state == MinecraftServerState::Eula {
+        return;
+    }
+    let stopped = *mc_state == MinecraftServerState::Off && status.is_some_and(|status| status.success());
+    if !stopped {
+        record_crash(data, status);
+    }
+    *mc_state = MinecraftServerState::Off;
+    *gen_state = GeneralState::Restart;
+}
+
+/// Waits a few seconds for the process to exit, a process that has closed its output but stays running is killed.
+fn wait_for_child(data: &shared_data::ServerSharedData) -> Option<ExitStatus> {
+    let start = Instant::now();
+    loop {
+        {
+            let mut child = data.child.lock().unwrap();
+            let child = child.as_mut()?;
+            if let Ok(Some(status)) = child.try_wait() {
+                return Some(status);
+            }
+            if start.elapsed() >= Duration::from_secs(5) {
+                let _ = child.kill();
+                return child.wait().ok();
+            }
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+fn record_crash(data: &shared_data::ServerSharedData, status: Option<ExitStatus>) {
+    let lines = data
+        .server_output
+        .lock()
+        .unwrap()
+        .iter()
+        .take(CRASH_REPORT_LINES)
+        .rev()
+        .map(|(_, line, stream)| match stream {
+            OutputStream::Stdout => line.replace("\n", ""),
+            OutputStream::Stderr => format!("[stderr] {}", line.replace("\n", "")),
+        })
+        .collect::<Vec<String>>();
+    let report = CrashReport {
+        time: unix_time(),
+        exit_code: status.and_then(|status| status.code()),
+        status: match status {
+            Some(status) => status.to_string(),
+            None => "unknown".to_string(),
+        },
+        lines,
+    };
+    println!(
+        "\x1b[0;31m[Crash]:\x1b[0m The Minecraft server exited unexpectedly with {}",
+        report.status
+    );
+    let file = format!("{}/crash-{}.txt", CRASH_REPORT_LOCATION, report.time);
+    let contents = format!(
+        "Time: {}\nStatus: {}\nLast {} lines of output:\n{}\n",
+        report.time,
+        report.status,
+        report.lines.len(),
+        report.lines.join("\n")
+    );
+    if fs::create_dir_all(CRASH_REPORT_LOCATION)
+        .and_then(|_| fs::write(&file, contents))
+        .is_err()
+    {
+        println!("Failed to write crash report: {}", file);
+    }
+    data.crashes.lock().unwrap().push(report);
+}
+
+/// Determines how many seconds to wait before restarting after a crash, the wait doubles with each recent crash.
+///
+/// Returns `None` once there have been `limit` crashes within `window` minutes, the server should not be restarted.
+pub fn crash_backoff(crashes: &[CrashReport], now: u64, limit: u32, window: u64, backoff: u64) -> Option<u64> {
+    let recent = crashes
+        .iter()
+        .filter(|crash| crash.time + window * 60 > now)
+        .count() as u32;
+    if recent >= limit {
+        return None;
+    }
+    let multiplier = 2u64.saturating_pow(recent.saturating_sub(1));
+    Some(backoff.saturating_mul(multiplier).min(MAX_CRASH_BACKOFF))
+}
+
+/// Reads a value from `server.properties` in the server directory.
+pub fn server_property(key: &str) -> Option<String> {
+    ServerProperties::load().ok()?.get(key).map(|value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_responses() {
+        let line = "[12:01:44] [Server thread/INFO]: Added Notch to the whitelist";
+        assert_eq!(line_message(line), "Added Notch to the whitelist");
+        assert!(is_command_response("whitelist add Notch", line_message(line)));
+        assert!(!is_command_response("whitelist add Notch", "Notch joined the game"));
+        assert!(is_command_response("notacommand", "Unknown or incomplete command, see below for error"));
+        assert!(is_command_response("notacommand", "notacommand<--[HERE]"));
+        assert!(is_command_response("say hello", "[Server] hello"));
+
+        // Players typing the server's answers into chat
+        assert!(!is_command_response("whitelist add Notch", "<Steve> Unknown or incomplete command"));
+        assert!(!is_command_response("kick Notch", "<Steve> No player was found"));
+        assert!(!is_command_response("whitelist add Notch", "[Steve] Added Notch to the whitelist"));
+
+        // The player count that the scheduler asks for with `list` while another command waits
+        let count = "There are 2 of a max of 20 players online: Notch, jeb_";
+        assert!(!is_command_response("whitelist list", count));
+        assert!(!is_command_response("banlist", count));
+        assert!(is_command_response("list", count));
+        assert!(is_command_response("list", "There are 0 out of maximum 20 players online."));
+        assert!(is_command_response("whitelist list", "There are 2 whitelisted player(s): Notch, jeb_"));
+        assert!(!is_command_response("list", "There are 2 whitelisted player(s): Notch, jeb_"));
+        assert!(is_command_response("banlist", "There are no bans"));
+        assert!(is_command_response("banlist", "There is 1 ban(s):"));
+    }
+
+    #[test]
+    fn server_flavors() {
+        assert_eq!(detect_flavor(Path::new("server/paper-1.20.4-435.jar")).name(), "paper");
+        assert_eq!(detect_flavor(Path::new("Waterfall.jar")).name(), "bungeecord");
+        assert_eq!(detect_flavor(Path::new("missing.jar")).name(), "vanilla");
+        let directory = b"PK\x01\x02....net/fabricmc/loader/impl/launch/server/FabricServerLauncher.class";
+        assert_eq!(flavor_from_entries(directory), Some("fabric"));
+        assert_eq!(flavor_from_entries(b"net/minecraft/bundler/Main.class"), None);
+
+        let velocity = flavor_named("velocity").unwrap();
+        assert_eq!(velocity.player_joined("[connected player] Notch (/127.0.0.1:51234) has connected"), Some("Notch"));
+        assert!(velocity.is_started("Done (1.52s)!"));
+        let bungee = flavor_named("waterfall").unwrap();
+        assert_eq!(bungee.player_left("[Notch] -> UpstreamBridge has disconnected"), Some("Notch"));
+        assert_eq!(bungee.stop_command(), "end");
+        let vanilla = flavor_named("vanilla").unwrap();
+        assert_eq!(vanilla.player_joined("Notch joined the game"), Some("Notch"));
+        assert_eq!(vanilla.player_joined("<Notch> I joined the game"), None);
+        assert!(!vanilla.has(Capability::Tps) && flavor_named("paper").unwrap().has(Capability::Tps));
+    }
+
+    #[test]
+    fn chat_messages() {
+        assert_eq!(parse_chat("<Notch> hello <there>"), Some((ChatKind::Chat, "Notch", "hello <there>")));
+        assert_eq!(parse_chat("[Not Secure] <Notch> hi"), Some((ChatKind::Chat, "Notch", "hi")));
+        assert_eq!(parse_chat("* Notch waves"), Some((ChatKind::Emote, "Notch", "waves")));
+        assert_eq!(parse_chat("[Server] Restarting soon"), Some((ChatKind::Say, "Server", "Restarting soon")));
+        assert_eq!(parse_chat("<Not a name> hi"), None);
+        assert_eq!(parse_chat("Notch joined the game"), None);
+    }
+
+    #[test]
+    fn player_details() {
+        assert_eq!(
+            parse_player_detail("UUID of player Notch is 069a79f4-44e9-4726-a5be-fca90e38aaf5"),
+            Some(PlayerDetail::Uuid("Notch", "069a79f4-44e9-4726-a5be-fca90e38aaf5"))
+        );
+        assert_eq!(
+            parse_player_detail("Notch[/127.0.0.1:51234] logged in with entity id 123 at (0.5, 64.0, 0.5)"),
+            Some(PlayerDetail::Address("Notch", "127.0.0.1"))
+        );
+        assert_eq!(
+            parse_player_detail("Notch lost connection: Disconnected"),
+            Some(PlayerDetail::Disconnected("Notch", "Disconnected"))
+        );
+        assert_eq!(parse_player_detail("<Notch> Notch lost connection: lol"), None);
+
+        let mut player = PlayerRecord::new("Notch");
+        player.sessions.push_back(PlayerSession { joined: 100, left: None, reason: None, ip: None });
+        assert_eq!(player.total_playtime(160), 60);
+        end_session(&mut player, 200, Some("Server stopped"));
+        assert!(!player.online());
+        assert_eq!(player.total_playtime(500), 100);
+        assert_eq!(player.sessions[0].reason.as_deref(), Some("Server stopped"));
+    }
+
+    #[test]
+    fn death_and_advancement_messages() {
+        let death = |message| match parse_death(message) {
+            Some((player, GameEventKind::Death { cause, killer, .. })) => Some((player, cause, killer)),
+            _ => None,
+        };
+        assert_eq!(
+            death("Notch was slain by Zombie using [Sword of Doom]"),
+            Some(("Notch", "slain".to_string(), Some("Zombie".to_string())))
+        );
+        assert_eq!(death("Notch fell from a high place"), Some(("Notch", "fall".to_string(), None)));
+        assert_eq!(
+            death("Notch walked into fire whilst fighting Blaze"),
+            Some(("Notch", "fire".to_string(), Some("Blaze".to_string())))
+        );
+        assert_eq!(
+            death("Notch was shot by a skull from Wither"),
+            Some(("Notch", "wither_skull".to_string(), Some("Wither".to_string())))
+        );
+        assert_eq!(
+            death("Notch drowned whilst trying to escape Drowned"),
+            Some(("Notch", "drowned".to_string(), Some("Drowned".to_string())))
+        );
+        assert_eq!(death("Notch died"), Some(("Notch", "generic".to_string(), None)));
+        assert_eq!(death("Notch diedd"), None);
+        assert_eq!(death("Notch joined the game"), None);
+        assert_eq!(
+            parse_advancement("Notch has completed the challenge [The End?]"),
+            Some((
+                "Notch",
+                GameEventKind::Advancement {
+                    kind: "challenge".to_string(),
+                    title: "The End?".to_string()
                 }
+            ))
+        );
+        assert_eq!(parse_advancement("Notch has made the advancement Stone Age"), None);
+    }
+
+    #[test]
+    fn alerts() {
+        assert_eq!(parse_lag("Can't keep up! Is the server overloaded? Running 2004ms or 40 ticks behind"), Some((2004, 40)));
+        assert_eq!(
+            alert_key("Can't keep up! Is the server overloaded? Running 2004ms or 40 ticks behind"),
+            alert_key("Can't keep up! Is the server overloaded? Running 5012ms or 100 ticks behind")
+        );
+
+        let data = shared_data::ServerSharedData::new();
+        let mut parser = LogParser::default();
+        let log = "\
+[12:02:01 ERROR]: Could not pass event PlayerJoinEvent to Example v1.0
+java.lang.NullPointerException: null
+\tat com.example.Listener.onJoin(Listener.java:12) ~[?:?]
+[12:02:02 INFO]: Notch left the game
+java.lang.IllegalStateException: not part of the error
+[12:02:05 ERROR]: Could not pass event PlayerJoinEvent to Example v1.0
+java.lang.NullPointerException: null
+[12:02:06 WARN]: Can't keep up! Is the server overloaded? Running 2004ms or 40 ticks behind
+";
+        for line in log.lines() {
+            record_alert(&data, &parser.parse(line));
+        }
+        let alerts = data.alerts.lock().unwrap();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].count, 2);
+        assert_eq!(alerts[0].trace, vec!["java.lang.NullPointerException: null".to_string()]);
+        assert_eq!(alerts[1].level, LogLevel::Warn);
+        assert!(alerts[1].open && !alerts[0].open);
+        assert_eq!(data.lag.lock().unwrap().skipped_ticks, 40);
+    }
+
+    #[test]
+    fn stderr_alerts() {
+        let data = shared_data::ServerSharedData::new();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        let mut parser = LogParser::default();
+        let log = "\
+Exception in thread \"Server thread\" java.lang.OutOfMemoryError: Java heap space
+\tat java.base/java.util.Arrays.copyOf(Arrays.java:3512)
+Caused by: java.lang.RuntimeException: out of memory
+\t... 3 more
+";
+        for line in log.lines() {
+            server_output_scanning(&parse_stderr_line(&mut parser, line), data.clone(), &sender, &Vanilla);
+        }
+        let alerts = data.alerts.lock().unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].level, LogLevel::Error);
+        assert!(alerts[0].message.contains("java.lang.OutOfMemoryError"));
+        assert_eq!(alerts[0].trace.len(), 3);
+    }
+
+    #[test]
+    fn performance() {
+        use PerformanceSource::*;
+        assert_eq!(
+            parse_performance("§6TPS from last 1m, 5m, 15m: §a*20.0, §a19.5, §a19.9"),
+            Some((Some(20.0), None, Paper))
+        );
+        assert_eq!(parse_performance("◴ 12.5/8.1/40.2, 11.9/7.5/40.2, 12.0/6.2/52.8"), Some((None, Some(12.5), Paper)));
+        assert_eq!(parse_performance("? 12.5/8.1/40.2, 11.9/7.5/40.2, 12.0/6.2/52.8"), Some((None, Some(12.5), Paper)));
+        assert_eq!(parse_performance("??? 12.5/8.1/40.2, 11.9/7.5/40.2, 12.0/6.2/52.8"), Some((None, Some(12.5), Paper)));
+        assert_eq!(parse_performance("Loaded 7 recipes, 1/2 done"), None);
+        assert_eq!(
+            parse_performance("Overall : Mean tick time: 1.234 ms. Mean TPS: 20.000"),
+            Some((Some(20.0), Some(1.234), Forge))
+        );
+        assert_eq!(parse_performance("Overall: 18.500 TPS (54.054 ms/tick)"), Some((Some(18.5), Some(54.054), Forge)));
+        assert_eq!(
+            parse_performance("Stopped debug profiling after 10.05 seconds and 196 ticks (19.50 ticks per second)"),
+            Some((Some(19.5), None, Profiler))
+        );
+        assert_eq!(
+            parse_performance("Stopped tick profiling after 10.00 second(s) and 200 tick(s) (20.00 tick(s) per second)"),
+            Some((Some(20.0), None, Profiler))
+        );
+        assert_eq!(parse_performance("Overall progress is good"), None);
+        assert_eq!(lag_tps(0, 60.0), 20.0);
+        assert_eq!(lag_tps(120, 60.0), 18.0);
+        assert_eq!(lag_tps(5000, 60.0), 0.0);
+    }
+
+    #[test]
+    fn player_lists() {
+        assert_eq!(
+            parse_player_list("There are 2 of a max of 20 players online: Notch, jeb_"),
+            Some((2, 20, Some(vec!["Notch", "jeb_"])))
+        );
+        assert_eq!(parse_player_list("There are 0 of a max of 20 players online: "), Some((0, 20, Some(vec![]))));
+        assert_eq!(parse_player_list("There are 0 of a max of 20 players online:"), Some((0, 20, Some(vec![]))));
+        assert_eq!(parse_player_list("There are 3 out of maximum 50 players online."), Some((3, 50, None)));
+        assert_eq!(parse_player_list("There are 1/20 players online:"), Some((1, 20, None)));
+        assert_eq!(parse_player_list("There are no bans"), None);
+
+        let online = vec!["Herobrine".to_string(), "jeb_".to_string()];
+        assert_eq!(
+            list_difference(&online, &["Notch", "jeb_"]),
+            (vec!["Notch".to_string()], vec!["Herobrine".to_string()])
+        );
+        assert_eq!(parse_list_group("default: Notch, [AFK]jeb_"), Some(vec!["Notch", "jeb_"]));
+        assert_eq!(parse_list_group("Notch joined the game"), None);
+    }
+
+    #[test]
+    fn two_line_player_list() {
+        let data = shared_data::ServerSharedData::new();
+        let (sender, _receiver) = std::sync::mpsc::channel();
+        *data.current_players.lock().unwrap() = vec!["Herobrine".to_string(), "jeb_".to_string()];
+        *data.current_player_count.lock().unwrap() = 2;
+        let mut parser = LogParser::default();
+        // Paper prints each group of names on its own line without a prefix
+        let log = "\
+[12:00:00 INFO]: There are 3 out of maximum 20 players online.
+admin: Notch
+default: jeb_, Dinnerbone
+[12:00:01 INFO]: There are 1 out of maximum 20 players online.
+[12:00:02 INFO]: Dinnerbone left the game
+";
+        for (index, line) in log.lines().enumerate() {
+            server_output_scanning(&parser.parse(line), data.clone(), &sender, &Paper);
+            if index == 2 {
+                assert_eq!(*data.current_players.lock().unwrap(), vec!["Notch", "jeb_", "Dinnerbone"]);
+                assert_eq!(*data.current_player_count.lock().unwrap(), 3);
+                let drift = data.player_drift.lock().unwrap();
+                assert_eq!(drift.back().map(|drift| drift.removed.clone()), Some(vec!["Herobrine".to_string()]));
             }
         }
+        // The names of the second list never came, so only its count is taken
+        assert!(data.pending_list.lock().unwrap().is_none());
+        assert_eq!(data.current_players.lock().unwrap().len(), 2);
+        assert_eq!(*data.current_player_count.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn startup_stages() {
+        assert_eq!(startup_stage("Preparing spawn area: 42%"), Some((StartupStage::PreparingSpawn, Some(42))));
+        assert_eq!(startup_stage("Preparing level \"world\""), Some((StartupStage::PreparingLevel, None)));
+        assert_eq!(startup_stage("Loaded 7 recipes"), Some((StartupStage::LoadingDatapacks, None)));
+        assert_eq!(
+            startup_stage("[Essentials] Loading server plugin Essentials v2.20.1"),
+            Some((StartupStage::LoadingPlugins, None))
+        );
+        assert_eq!(startup_stage("Loaded my favourite things"), None);
+        assert_eq!(reported_startup("Done (12.345s)! For help, type \"help\""), Some(12.345));
+
+        let mut progress = shared_data::StartupProgress::new();
+        progress.advance(StartupStage::PreparingSpawn, Some(50));
+        assert_eq!(progress.percent(), 72);
+        // Stages printed late do not move the progress backwards
+        progress.advance(StartupStage::LoadingDatapacks, None);
+        assert!(progress.stage == StartupStage::PreparingSpawn);
+    }
+
+    #[test]
+    fn crash_backoff_limits() {
+        let crash = |time| CrashReport {
+            time,
+            exit_code: Some(1),
+            status: "exit status: 1".to_string(),
+            lines: Vec::new(),
+        };
+        let crashes = vec![crash(1000), crash(1400), crash(1500)];
+        assert_eq!(crash_backoff(&crashes[..1], 1010, 5, 10, 5), Some(5));
+        assert_eq!(crash_backoff(&crashes, 1510, 5, 10, 5), Some(20));
+        // Crashes older than the window are not counted
+        assert_eq!(crash_backoff(&crashes, 1610, 5, 10, 5), Some(10));
+        assert_eq!(crash_backoff(&crashes, 1510, 3, 10, 5), None);
     }
 }
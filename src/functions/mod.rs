@@ -1,5 +1,13 @@
 pub mod configuration;
+pub mod json;
+pub mod log_parser;
 pub mod minecraft_related;
+pub mod player_lists;
+pub mod properties;
+pub mod protocol;
+pub mod rcon;
+pub mod scheduler;
 pub mod server_interactions;
 pub mod shared_data;
+pub mod system_metrics;
 pub mod web_server;
@@ -0,0 +1,264 @@
+//! The server's whitelist, operators and bans, kept in `whitelist.json`, `ops.json`, `banned-players.json` and
+//! `banned-ips.json`.
+//!
+//! While the server is running it owns these files and writes them whenever they change, so changes are made through
+//! its commands. While it is off the files are edited directly, with players' UUIDs found in `usercache.json`.
+use std::{fs, io, net::IpAddr};
+
+use super::json::Json;
+use super::minecraft_related::server_property;
+use super::scheduler::{local_offset, LocalTime};
+
+/// Where the server remembers the UUIDs of players that have joined.
+const USERCACHE_LOCATION: &str = "usercache.json";
+
+/// The reason that the server gives to bans without one.
+const DEFAULT_BAN_REASON: &str = "Banned by an operator.";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayerList {
+    Whitelist,
+    Ops,
+    Bans,
+    IpBans,
+}
+
+impl PlayerList {
+    /// The list with the name used in the API.
+    pub fn from_name(name: &str) -> Option<PlayerList> {
+        match name {
+            "whitelist" => Some(PlayerList::Whitelist),
+            "ops" => Some(PlayerList::Ops),
+            "bans" => Some(PlayerList::Bans),
+            "ip-bans" => Some(PlayerList::IpBans),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlayerList::Whitelist => "whitelist",
+            PlayerList::Ops => "ops",
+            PlayerList::Bans => "bans",
+            PlayerList::IpBans => "ip-bans",
+        }
+    }
+
+    /// The file in the server directory that the list is kept in.
+    pub fn file(&self) -> &'static str {
+        match self {
+            PlayerList::Whitelist => "whitelist.json",
+            PlayerList::Ops => "ops.json",
+            PlayerList::Bans => "banned-players.json",
+            PlayerList::IpBans => "banned-ips.json",
+        }
+    }
+
+    /// The console command that adds `target` to the list while the server is running.
+    pub fn add_command(&self, target: &str, reason: Option<&str>) -> String {
+        let command = match self {
+            PlayerList::Whitelist => format!("whitelist add {}", target),
+            PlayerList::Ops => format!("op {}", target),
+            PlayerList::Bans => format!("ban {}", target),
+            PlayerList::IpBans => format!("ban-ip {}", target),
+        };
+        match reason {
+            Some(reason) if matches!(self, PlayerList::Bans | PlayerList::IpBans) => format!("{} {}", command, reason),
+            _ => command,
+        }
+    }
+
+    /// The console command that removes `target` from the list while the server is running.
+    pub fn remove_command(&self, target: &str) -> String {
+        match self {
+            PlayerList::Whitelist => format!("whitelist remove {}", target),
+            PlayerList::Ops => format!("deop {}", target),
+            PlayerList::Bans => format!("pardon {}", target),
+            PlayerList::IpBans => format!("pardon-ip {}", target),
+        }
+    }
+}
+
+/// Why an entry could not be added to a list's file.
+#[derive(Debug, PartialEq)]
+pub enum ListError {
+    /// The player has never joined, so their UUID is not known.
+    UnknownPlayer(String),
+    InvalidAddress(String),
+}
+
+/// What is given along with a player when they are added to a list, only bans use the reason and expiry and only
+/// operators use the level.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ListChange {
+    pub reason: Option<String>,
+    /// Seconds since the Unix epoch at which a ban ends, bans without one are forever.
+    pub expires: Option<u64>,
+    pub level: Option<u32>,
+}
+
+/// Reads the entries of a list, a file that does not exist yet is an empty list.
+pub fn load_list(list: PlayerList) -> io::Result<Vec<Json>> {
+    let text = match fs::read_to_string(list.file()) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    match Json::parse(&text) {
+        Some(Json::Array(entries)) => Ok(entries),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a JSON array", list.file()))),
+    }
+}
+
+/// Writes the entries of a list through a temporary file, formatted the way the server writes them.
+pub fn save_list(list: PlayerList, entries: &Json) -> io::Result<()> {
+    let temporary = format!("{}.tmp", list.file());
+    fs::write(&temporary, entries.pretty())?;
+    fs::rename(&temporary, list.file())
+}
+
+/// Finds the UUID and the correctly cased name of a player that has joined the server before.
+pub fn cached_player(name: &str) -> Option<(String, String)> {
+    match Json::parse(&fs::read_to_string(USERCACHE_LOCATION).ok()?)? {
+        Json::Array(players) => players.iter().find_map(|player| {
+            let cached = player.get("name")?.as_str()?;
+            if cached.eq_ignore_ascii_case(name) {
+                Some((player.get("uuid")?.as_str()?.to_string(), cached.to_string()))
+            } else {
+                None
+            }
+        }),
+        _ => None,
+    }
+}
+
+/// Formats a time the way the server writes the times of bans, `2024-01-31 18:30:00 +0000` in local time.
+pub fn ban_time(unix_time: u64) -> String {
+    let offset = local_offset();
+    let time = LocalTime::from_minute((unix_time as i64 + offset).div_euclid(60));
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} {}{:02}{:02}",
+        time.year,
+        time.month,
+        time.day,
+        time.hour,
+        time.minute,
+        unix_time % 60,
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 3600,
+        offset.abs() % 3600 / 60
+    )
+}
+
+/// Whether an entry is for `target`, a player's name or UUID, or an address for IP bans.
+fn entry_matches(list: PlayerList, entry: &Json, target: &str) -> bool {
+    let field = |name: &str| entry.get(name).and_then(Json::as_str);
+    match list {
+        PlayerList::IpBans => field("ip") == Some(target),
+        _ => field("name").is_some_and(|name| name.eq_ignore_ascii_case(target)) || field("uuid") == Some(target),
+    }
+}
+
+/// Adds `target` to a list's entries as the server would, returns false if it was already on the list.
+pub fn add_entry(
+    list: PlayerList,
+    entries: &mut Vec<Json>,
+    target: &str,
+    change: &ListChange,
+    now: u64,
+) -> Result<bool, ListError> {
+    if entries.iter().any(|entry| entry_matches(list, entry, target)) {
+        return Ok(false);
+    }
+    let text = |text: &str| Json::Text(text.to_string());
+    let ban = || {
+        vec![
+            ("created".to_string(), text(&ban_time(now))),
+            ("source".to_string(), text("Server")),
+            ("expires".to_string(), text(&change.expires.map_or("forever".to_string(), ban_time))),
+            ("reason".to_string(), text(change.reason.as_deref().unwrap_or(DEFAULT_BAN_REASON))),
+        ]
+    };
+    let mut fields = match list {
+        PlayerList::IpBans => {
+            if target.parse::<IpAddr>().is_err() {
+                return Err(ListError::InvalidAddress(target.to_string()));
+            }
+            vec![("ip".to_string(), text(target))]
+        }
+        _ => {
+            let (uuid, name) = cached_player(target).ok_or_else(|| ListError::UnknownPlayer(target.to_string()))?;
+            // Players found by name might already be listed under their UUID
+            if entries.iter().any(|entry| entry_matches(list, entry, &uuid)) {
+                return Ok(false);
+            }
+            vec![("uuid".to_string(), text(&uuid)), ("name".to_string(), text(&name))]
+        }
+    };
+    match list {
+        PlayerList::Whitelist => {}
+        PlayerList::Ops => {
+            let level = change
+                .level
+                .or_else(|| server_property("op-permission-level").and_then(|level| level.parse().ok()))
+                .unwrap_or(4);
+            fields.push(("level".to_string(), Json::Number(level as f64)));
+            fields.push(("bypassesPlayerLimit".to_string(), Json::Bool(false)));
+        }
+        PlayerList::Bans | PlayerList::IpBans => fields.extend(ban()),
+    }
+    entries.push(Json::Object(fields));
+    Ok(true)
+}
+
+/// Removes `target` from a list's entries, returns false if it was not on the list.
+pub fn remove_entry(list: PlayerList, entries: &mut Vec<Json>, target: &str) -> bool {
+    let before = entries.len();
+    entries.retain(|entry| !entry_matches(list, entry, target));
+    entries.len() != before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_entries() {
+        let mut entries = match Json::parse(
+            "[{\"ip\": \"10.0.0.1\", \"created\": \"2024-01-01 00:00:00 +0000\", \"source\": \"Server\", \"expires\": \"forever\", \"reason\": \"Spam\"}]",
+        ) {
+            Some(Json::Array(entries)) => entries,
+            _ => panic!("The list did not parse"),
+        };
+        let change = ListChange { reason: Some("Griefing".to_string()), expires: None, level: None };
+        assert_eq!(add_entry(PlayerList::IpBans, &mut entries, "10.0.0.1", &change, 0), Ok(false));
+        assert_eq!(
+            add_entry(PlayerList::IpBans, &mut entries, "not an address", &change, 0),
+            Err(ListError::InvalidAddress("not an address".to_string()))
+        );
+        assert_eq!(add_entry(PlayerList::IpBans, &mut entries, "2001:db8::1", &change, 0), Ok(true));
+        assert_eq!(entries[1].get("reason").and_then(Json::as_str), Some("Griefing"));
+        assert_eq!(entries[1].get("expires").and_then(Json::as_str), Some("forever"));
+        assert!(remove_entry(PlayerList::IpBans, &mut entries, "10.0.0.1"));
+        assert!(!remove_entry(PlayerList::IpBans, &mut entries, "10.0.0.1"));
+        assert_eq!(entries.len(), 1);
+
+        let whitelist = Json::Array(vec![Json::Object(vec![
+            ("uuid".to_string(), Json::Text("069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string())),
+            ("name".to_string(), Json::Text("Notch".to_string())),
+        ])]);
+        assert_eq!(
+            whitelist.pretty(),
+            "[\n  {\n    \"uuid\": \"069a79f4-44e9-4726-a5be-fca90e38aaf5\",\n    \"name\": \"Notch\"\n  }\n]"
+        );
+        let mut whitelist = match whitelist {
+            Json::Array(entries) => entries,
+            _ => unreachable!(),
+        };
+        assert!(entry_matches(PlayerList::Whitelist, &whitelist[0], "notch"));
+        assert!(remove_entry(PlayerList::Whitelist, &mut whitelist, "069a79f4-44e9-4726-a5be-fca90e38aaf5"));
+        assert_eq!(Json::Array(whitelist).pretty(), "[]");
+        assert_eq!(PlayerList::Bans.add_command("Notch", Some("Griefing")), "ban Notch Griefing");
+        assert_eq!(PlayerList::Ops.add_command("Notch", Some("Griefing")), "op Notch");
+    }
+}
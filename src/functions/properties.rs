@@ -0,0 +1,338 @@
+//! Reading and editing `server.properties` in the Java properties format.
+//!
+//! Every line of the file is kept as it was read, so comments, ordering and the formatting of untouched properties
+//! survive an edit. Only the lines of properties that are changed are written out again, escaped the way Java would.
+use std::{fmt, fs, io, path::Path};
+
+/// Where the server's properties are kept, relative to the server directory.
+pub const PROPERTIES_LOCATION: &str = "server.properties";
+
+/// The characters that Java skips around keys and at the start of lines.
+const WHITESPACE: [char; 3] = [' ', '\t', '\x0c'];
+
+/// The kinds of values that the server accepts for a property.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PropertyKind {
+    Boolean,
+    /// A whole number within the inclusive range.
+    Integer(i64, i64),
+    Choice(&'static [&'static str]),
+    Text,
+}
+
+impl PropertyKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PropertyKind::Boolean => "boolean",
+            PropertyKind::Integer(_, _) => "integer",
+            PropertyKind::Choice(_) => "choice",
+            PropertyKind::Text => "text",
+        }
+    }
+}
+
+const PORT: PropertyKind = PropertyKind::Integer(1, 65535);
+const COUNT: PropertyKind = PropertyKind::Integer(0, i32::MAX as i64);
+
+/// The properties of vanilla servers that only accept some values, any others are taken as text.
+const KNOWN_PROPERTIES: &[(&str, PropertyKind)] = &[
+    ("accepts-transfers", PropertyKind::Boolean),
+    ("allow-flight", PropertyKind::Boolean),
+    ("allow-nether", PropertyKind::Boolean),
+    ("broadcast-console-to-ops", PropertyKind::Boolean),
+    ("broadcast-rcon-to-ops", PropertyKind::Boolean),
+    ("difficulty", PropertyKind::Choice(&["peaceful", "easy", "normal", "hard"])),
+    ("enable-command-block", PropertyKind::Boolean),
+    ("enable-jmx-monitoring", PropertyKind::Boolean),
+    ("enable-query", PropertyKind::Boolean),
+    ("enable-rcon", PropertyKind::Boolean),
+    ("enable-status", PropertyKind::Boolean),
+    ("enforce-secure-profile", PropertyKind::Boolean),
+    ("enforce-whitelist", PropertyKind::Boolean),
+    ("entity-broadcast-range-percentage", PropertyKind::Integer(10, 1000)),
+    ("force-gamemode", PropertyKind::Boolean),
+    ("function-permission-level", PropertyKind::Integer(1, 4)),
+    ("gamemode", PropertyKind::Choice(&["survival", "creative", "adventure", "spectator"])),
+    ("generate-structures", PropertyKind::Boolean),
+    ("hardcore", PropertyKind::Boolean),
+    ("hide-online-players", PropertyKind::Boolean),
+    ("log-ips", PropertyKind::Boolean),
+    ("max-chained-neighbor-updates", PropertyKind::Integer(i32::MIN as i64, i32::MAX as i64)),
+    ("max-players", COUNT),
+    ("max-tick-time", PropertyKind::Integer(-1, i64::MAX)),
+    ("max-world-size", PropertyKind::Integer(1, 29999984)),
+    ("network-compression-threshold", PropertyKind::Integer(-1, i32::MAX as i64)),
+    ("online-mode", PropertyKind::Boolean),
+    ("op-permission-level", PropertyKind::Integer(0, 4)),
+    ("player-idle-timeout", COUNT),
+    ("prevent-proxy-connections", PropertyKind::Boolean),
+    ("pvp", PropertyKind::Boolean),
+    ("query.port", PORT),
+    ("rate-limit", COUNT),
+    ("rcon.port", PORT),
+    ("require-resource-pack", PropertyKind::Boolean),
+    ("server-port", PORT),
+    ("simulation-distance", PropertyKind::Integer(3, 32)),
+    ("spawn-animals", PropertyKind::Boolean),
+    ("spawn-monsters", PropertyKind::Boolean),
+    ("spawn-npcs", PropertyKind::Boolean),
+    ("spawn-protection", COUNT),
+    ("sync-chunk-writes", PropertyKind::Boolean),
+    ("use-native-transport", PropertyKind::Boolean),
+    ("view-distance", PropertyKind::Integer(3, 32)),
+    ("white-list", PropertyKind::Boolean),
+];
+
+/// The kind of value that the server accepts for the property `key`.
+pub fn property_kind(key: &str) -> PropertyKind {
+    KNOWN_PROPERTIES
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map_or(PropertyKind::Text, |(_, kind)| *kind)
+}
+
+/// Checks that `value` is one that the server accepts for `key`, describing the problem if not.
+pub fn validate_property(key: &str, value: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("The name of a property can not be empty".to_string());
+    }
+    match property_kind(key) {
+        PropertyKind::Boolean if value != "true" && value != "false" => {
+            Err(format!("{} must be true or false", key))
+        }
+        PropertyKind::Integer(min, max) => match value.parse::<i64>() {
+            Ok(number) if number >= min && number <= max => Ok(()),
+            _ => Err(format!("{} must be a whole number from {} to {}", key, min, max)),
+        },
+        PropertyKind::Choice(choices) if !choices.contains(&value) => {
+            Err(format!("{} must be one of {}", key, choices.join(", ")))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A line of the file as it was read, along with the property on it if it is not a comment or blank.
+#[derive(Clone, Debug, PartialEq)]
+struct PropertyLine {
+    /// The text of the line, lines continued with a backslash include the lines after them.
+    raw: String,
+    property: Option<(String, String)>,
+}
+
+/// The contents of a properties file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServerProperties {
+    lines: Vec<PropertyLine>,
+}
+
+impl ServerProperties {
+    pub fn parse(text: &str) -> ServerProperties {
+        let mut lines = Vec::new();
+        let mut physical = text.lines();
+        while let Some(line) = physical.next() {
+            let trimmed = line.trim_start_matches(WHITESPACE);
+            if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('!') {
+                lines.push(PropertyLine { raw: line.to_string(), property: None });
+                continue;
+            }
+            let mut raw = line.to_string();
+            let mut logical = trimmed.to_string();
+            // A line ending in an odd number of backslashes carries on onto the next line
+            while logical.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1 {
+                logical.pop();
+                match physical.next() {
+                    Some(next) => {
+                        raw.push('\n');
+                        raw.push_str(next);
+                        logical.push_str(next.trim_start_matches(WHITESPACE));
+                    }
+                    None => break,
+                }
+            }
+            lines.push(PropertyLine { raw, property: Some(split_property(&logical)) });
+        }
+        ServerProperties { lines }
+    }
+
+    /// Reads the server's properties, a file that does not exist yet has no properties.
+    pub fn load() -> io::Result<ServerProperties> {
+        match fs::read_to_string(PROPERTIES_LOCATION) {
+            Ok(text) => Ok(ServerProperties::parse(&text)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(ServerProperties::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Writes the properties to the server's file, through a temporary file so that the server never reads half.
+    pub fn save(&self) -> io::Result<()> {
+        let temporary = format!("{}.tmp", PROPERTIES_LOCATION);
+        fs::write(&temporary, self.to_string())?;
+        fs::rename(&temporary, Path::new(PROPERTIES_LOCATION))
+    }
+
+    /// The value of a property, Java uses the last of repeated keys.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines.iter().rev().find_map(|line| match &line.property {
+            Some((name, value)) if name == key => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The properties in the order that they are first in the file, a key that is repeated is only given once.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        let mut seen = Vec::new();
+        self.lines.iter().filter_map(move |line| {
+            let (key, _) = line.property.as_ref()?;
+            if seen.contains(&key) {
+                return None;
+            }
+            seen.push(key);
+            Some((key.as_str(), self.get(key)?))
+        })
+    }
+
+    /// Sets a property, rewriting only its own line, new properties are added to the end. Returns whether the value
+    /// changed.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        if self.get(key) == Some(value) {
+            return false;
+        }
+        let line = PropertyLine {
+            raw: format!("{}={}", escape(key, true), escape(value, false)),
+            property: Some((key.to_string(), value.to_string())),
+        };
+        // Java uses the last of repeated keys, so every one of them is given the new value
+        let mut found = false;
+        for existing in self.lines.iter_mut() {
+            if existing.property.as_ref().is_some_and(|(name, _)| name == key) {
+                *existing = line.clone();
+                found = true;
+            }
+        }
+        if !found {
+            self.lines.push(line);
+        }
+        true
+    }
+}
+
+impl fmt::Display for ServerProperties {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line.raw)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a logical line into its key and value, the key ends at the first unescaped `=`, `:` or whitespace.
+fn split_property(line: &str) -> (String, String) {
+    let mut escaped = false;
+    let end = line
+        .char_indices()
+        .find(|(_, c)| {
+            let separator = !escaped && matches!(c, '=' | ':' | ' ' | '\t' | '\x0c');
+            escaped = !escaped && *c == '\\';
+            separator
+        })
+        .map_or(line.len(), |(index, _)| index);
+    let mut value = line[end..].trim_start_matches(WHITESPACE);
+    if let Some(rest) = value.strip_prefix('=').or_else(|| value.strip_prefix(':')) {
+        value = rest.trim_start_matches(WHITESPACE);
+    }
+    (unescape(&line[..end]), unescape(value))
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('f') => result.push('\x0c'),
+            Some('u') => {
+                let hex = chars.by_ref().take(4).collect::<String>();
+                // Characters outside of the first plane are written as two escaped surrogates
+                let mut units = vec![u16::from_str_radix(&hex, 16).unwrap_or(0xFFFD)];
+                if (0xD800..0xDC00).contains(&units[0]) && chars.as_str().starts_with("\\u") {
+                    let low = u16::from_str_radix(chars.as_str().get(2..6).unwrap_or(""), 16).unwrap_or(0);
+                    if (0xDC00..0xE000).contains(&low) {
+                        units.push(low);
+                        chars.by_ref().nth(5);
+                    }
+                }
+                result.extend(char::decode_utf16(units).map(|c| c.unwrap_or('\u{FFFD}')));
+            }
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    result
+}
+
+/// Escapes text the way Java writes properties, spaces are only escaped in keys and at the start of values.
+fn escape(text: &str, key: bool) -> String {
+    let mut result = String::new();
+    for (index, c) in text.chars().enumerate() {
+        match c {
+            ' ' if key || index == 0 => result.push_str("\\ "),
+            '\t' => result.push_str("\\t"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\x0c' => result.push_str("\\f"),
+            '=' | ':' | '#' | '!' | '\\' => {
+                result.push('\\');
+                result.push(c);
+            }
+            ' '..='~' => result.push(c),
+            _ => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    result.push_str(&format!("\\u{:04X}", unit));
+                }
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_properties() {
+        let text = "#Minecraft server properties\n#Mon Jan 01 00:00:00 UTC 2024\nmotd=\\u00A7aA Minecraft Server \\u2603\nlevel-name = my_world\n\n! An old comment\nresource-pack=https\\://example.com/pack.zip\nlevel-seed:\\ 42\\\n    00\nmax-players=20\n";
+        let mut properties = ServerProperties::parse(text);
+        assert_eq!(properties.to_string(), text);
+        assert_eq!(properties.get("motd"), Some("§aA Minecraft Server ☃"));
+        assert_eq!(properties.get("level-name"), Some("my_world"));
+        assert_eq!(properties.get("resource-pack"), Some("https://example.com/pack.zip"));
+        assert_eq!(properties.get("level-seed"), Some(" 4200"));
+        assert_eq!(properties.entries().count(), 5);
+
+        assert!(properties.set("max-players", "30"));
+        assert!(!properties.set("max-players", "30"));
+        assert!(properties.set("motd", "A: \"new\" motd ☃"));
+        assert!(properties.set("view-distance", "12"));
+        assert_eq!(
+            properties.to_string(),
+            "#Minecraft server properties\n#Mon Jan 01 00:00:00 UTC 2024\nmotd=A\\: \"new\" motd \\u2603\nlevel-name = my_world\n\n! An old comment\nresource-pack=https\\://example.com/pack.zip\nlevel-seed:\\ 42\\\n    00\nmax-players=30\nview-distance=12\n"
+        );
+        assert_eq!(ServerProperties::parse(&properties.to_string()), properties);
+        assert_eq!(unescape("\\uD83D\\uDE00"), "😀");
+        assert_eq!(escape("😀", false), "\\uD83D\\uDE00");
+
+        assert_eq!(validate_property("max-players", "30"), Ok(()));
+        assert!(validate_property("max-players", "-1").is_err());
+        assert!(validate_property("view-distance", "40").is_err());
+        assert!(validate_property("difficulty", "nightmare").is_err());
+        assert!(validate_property("pvp", "yes").is_err());
+        assert_eq!(validate_property("some-mod-setting", "anything"), Ok(()));
+    }
+}
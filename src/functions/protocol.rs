@@ -0,0 +1,712 @@
+//! The protocols that server lists use to ask about a server: the Server List Ping on the game port and Query over
+//! UDP. The monitor can ask other servers with both, and answer both itself while its server is down.
+use std::{
+    io::{self, Read, Write},
+    net::{IpAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::configuration::Configuration;
+use super::json::{json_escape, Json};
+use super::minecraft_related::{server_property, strip_formatting};
+use super::scheduler::unix_time;
+use super::shared_data::{self, GeneralState, MinecraftServerState, StartupStage};
+use super::web_server::RequestSlot;
+
+/// The port that Minecraft servers accept players on when `server-port` is not set.
+const DEFAULT_GAME_PORT: u16 = 25565;
+
+/// The largest packet that is read, the status of a server with a large icon is well below this.
+const MAX_PACKET: i32 = 1 << 21;
+
+/// The types of Query packets, a handshake for a challenge token and a request for the basic or full stat.
+const QUERY_HANDSHAKE: u8 = 0x09;
+const QUERY_STAT: u8 = 0x00;
+
+/// The bytes before the values of a full stat.
+const QUERY_PADDING: [u8; 11] = *b"splitnum\x00\x80\x00";
+
+/// How long a Query challenge token stays valid.
+const QUERY_TOKEN_LIFETIME: Duration = Duration::from_secs(30);
+
+/// The longest that the placeholder waits on each packet from a client.
+const PLACEHOLDER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most clients that the placeholder answers at once, any more are disconnected straight away.
+const MAX_PLACEHOLDER_CLIENTS: usize = 16;
+
+/// The port that the server accepts players on, from `server-port` in `server.properties`.
+pub fn game_port() -> u16 {
+    server_property("server-port")
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_GAME_PORT)
+}
+
+/// What a server reports about itself through the Server List Ping.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerStatus {
+    pub version: String,
+    pub protocol: i64,
+    pub online: u32,
+    pub max: u32,
+    /// Some of the players online, servers can hide these or fill them with other text.
+    pub sample: Vec<String>,
+    /// The message of the day without its formatting.
+    pub motd: String,
+    /// The round trip of the ping after the status, if the server answered it.
+    pub latency: Option<Duration>,
+}
+
+/// Asks a server for its status with the Server List Ping, the handshake and status request followed by a ping.
+///
+/// This works for servers of any version since 1.7, along with proxies, and does not depend on the monitor running
+/// the server.
+pub fn server_list_ping(host: &str, port: u16, timeout: Duration) -> io::Result<ServerStatus> {
+    let address = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "The address could not be resolved"))?;
+    let mut stream = TcpStream::connect_timeout(&address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    // Handshake with the protocol version left unknown, then ask for the status
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, 0x00);
+    write_varint(&mut handshake, -1);
+    write_string(&mut handshake, host);
+    handshake.extend_from_slice(&port.to_be_bytes());
+    write_varint(&mut handshake, 1);
+    write_packet(&mut stream, &handshake)?;
+    write_packet(&mut stream, &[0x00])?;
+
+    let response = read_packet(&mut stream)?;
+    let mut response = response.as_slice();
+    if read_varint(&mut response)? != 0x00 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "The server did not answer with its status"));
+    }
+    let length = read_varint(&mut response)? as usize;
+    let json = String::from_utf8_lossy(response.get(..length).unwrap_or(response)).to_string();
+    let mut status = parse_status(&json)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "The status could not be read"))?;
+
+    // Some servers close the connection after the status instead of answering the ping
+    let sent = Instant::now();
+    let mut ping = vec![0x01];
+    ping.extend_from_slice(&(unix_time() as i64).to_be_bytes());
+    if write_packet(&mut stream, &ping).is_ok() {
+        if let Ok(pong) = read_packet(&mut stream) {
+            if pong == ping {
+                status.latency = Some(sent.elapsed());
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// Reads the JSON of a status response.
+fn parse_status(json: &str) -> Option<ServerStatus> {
+    let status = Json::parse(json)?;
+    let players = status.get("players");
+    Some(ServerStatus {
+        version: status.get("version")?.get("name")?.as_str()?.to_string(),
+        protocol: status.get("version")?.get("protocol")?.as_f64()? as i64,
+        online: players.and_then(|players| players.get("online")?.as_f64()).unwrap_or(0.0) as u32,
+        max: players.and_then(|players| players.get("max")?.as_f64()).unwrap_or(0.0) as u32,
+        sample: match players.and_then(|players| players.get("sample")) {
+            Some(Json::Array(sample)) => sample
+                .iter()
+                .filter_map(|player| Some(player.get("name")?.as_str()?.to_string()))
+                .collect(),
+            _ => Vec::new(),
+        },
+        motd: status.get("description").map_or(String::new(), |description| strip_formatting(&chat_text(description))),
+        latency: None,
+    })
+}
+
+/// Joins the text of a chat component along with the components in its `extra`.
+fn chat_text(component: &Json) -> String {
+    match component {
+        Json::Text(text) => text.clone(),
+        Json::Array(components) => components.iter().map(chat_text).collect(),
+        Json::Object(_) => {
+            let mut text = component.get("text").and_then(Json::as_str).unwrap_or("").to_string();
+            if let Some(extra) = component.get("extra") {
+                text.push_str(&chat_text(extra));
+            }
+            text
+        }
+        _ => String::new(),
+    }
+}
+
+/// Writes a packet with its length in front.
+fn write_packet(stream: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    let mut packet = Vec::new();
+    write_varint(&mut packet, data.len() as i32);
+    packet.extend_from_slice(data);
+    stream.write_all(&packet)
+}
+
+/// Reads a packet from after its length.
+fn read_packet(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+    let length = read_varint(stream)?;
+    if !(0..=MAX_PACKET).contains(&length) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "The packet length is not valid"));
+    }
+    let mut packet = vec![0; length as usize];
+    stream.read_exact(&mut packet)?;
+    Ok(packet)
+}
+
+/// Writes a number as a VarInt, seven bits at a time with the top bit set on all but the last byte.
+fn write_varint(buffer: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buffer.push(value as u8);
+            return;
+        }
+        buffer.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+fn read_varint(stream: &mut impl Read) -> io::Result<i32> {
+    let mut value = 0u32;
+    for position in 0..5 {
+        let mut byte = [0];
+        stream.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7F) as u32) << (7 * position);
+        if byte[0] & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "The VarInt is too long"))
+}
+
+fn write_string(buffer: &mut Vec<u8>, text: &str) {
+    write_varint(buffer, text.len() as i32);
+    buffer.extend_from_slice(text.as_bytes());
+}
+
+/// What a server reports about itself through the GameSpy4 Query protocol, the version, plugins and players are only
+/// given by a full stat.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueryStatus {
+    pub motd: String,
+    pub game_type: String,
+    pub map: String,
+    pub online: u32,
+    pub max: u32,
+    pub host_port: u16,
+    pub host_ip: String,
+    pub version: Option<String>,
+    /// The server software and its plugins, `Paper on 1.20.4: WorldEdit 7.2.15; Essentials 2.20.1`.
+    pub plugins: Option<String>,
+    pub players: Vec<String>,
+}
+
+/// The port that the server answers Query on, from `query.port` in `server.properties`.
+pub fn query_port() -> u16 {
+    server_property("query.port")
+        .and_then(|port| port.parse().ok())
+        .unwrap_or_else(game_port)
+}
+
+/// Asks a server for its basic or full stat over UDP, after a handshake for the challenge token.
+pub fn query_server(host: &str, port: u16, full: bool, timeout: Duration) -> io::Result<QueryStatus> {
+    let address = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "The address could not be resolved"))?;
+    let socket = UdpSocket::bind(if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect(address)?;
+    // Servers only look at the low four bits of each byte of the session
+    let session = (std::process::id() as i32 ^ unix_time() as i32) & 0x0F0F0F0F;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "The server did not answer the query");
+
+    let mut handshake = vec![0xFE, 0xFD, QUERY_HANDSHAKE];
+    handshake.extend_from_slice(&session.to_be_bytes());
+    socket.send(&handshake)?;
+    let mut buffer = [0; 65535];
+    let length = socket.recv(&mut buffer)?;
+    let token = query_payload(&buffer[..length], QUERY_HANDSHAKE, session)
+        .and_then(|payload| c_strings(payload).next())
+        .and_then(|token| token.parse::<i32>().ok())
+        .ok_or_else(invalid)?;
+
+    let mut request = vec![0xFE, 0xFD, QUERY_STAT];
+    request.extend_from_slice(&session.to_be_bytes());
+    request.extend_from_slice(&token.to_be_bytes());
+    if full {
+        request.extend_from_slice(&[0; 4]);
+    }
+    socket.send(&request)?;
+    let length = socket.recv(&mut buffer)?;
+    let payload = query_payload(&buffer[..length], QUERY_STAT, session).ok_or_else(invalid)?;
+    if full {
+        parse_full_stat(payload)
+    } else {
+        parse_basic_stat(payload)
+    }
+    .ok_or_else(invalid)
+}
+
+/// Finds what follows the type and session of a response, if it is the expected type for the session.
+fn query_payload(response: &[u8], kind: u8, session: i32) -> Option<&[u8]> {
+    if response.len() < 5 || response[0] != kind || response[1..5] != session.to_be_bytes() {
+        return None;
+    }
+    Some(&response[5..])
+}
+
+/// Splits data into the null terminated strings that it is made of.
+fn c_strings(data: &[u8]) -> impl Iterator<Item = String> + '_ {
+    data.split(|byte| *byte == 0).map(|text| String::from_utf8_lossy(text).to_string())
+}
+
+fn parse_basic_stat(payload: &[u8]) -> Option<QueryStatus> {
+    let mut strings = payload.splitn(6, |byte| *byte == 0);
+    let mut text = || strings.next().map(|text| String::from_utf8_lossy(text).to_string());
+    let (motd, game_type, map, online, max) = (text()?, text()?, text()?, text()?, text()?);
+    // The port is the only field that is not a string
+    let rest = strings.next()?;
+    let host_port = u16::from_le_bytes([*rest.first()?, *rest.get(1)?]);
+    Some(QueryStatus {
+        motd,
+        game_type,
+        map,
+        online: online.parse().ok()?,
+        max: max.parse().ok()?,
+        host_port,
+        host_ip: c_strings(&rest[2..]).next().unwrap_or_default(),
+        ..QueryStatus::default()
+    })
+}
+
+fn parse_full_stat(payload: &[u8]) -> Option<QueryStatus> {
+    let mut strings = c_strings(payload.get(QUERY_PADDING.len()..)?);
+    let mut values = Vec::new();
+    loop {
+        let key = strings.next()?;
+        if key.is_empty() {
+            break;
+        }
+        values.push((key, strings.next()?));
+    }
+    let value = |key: &str| values.iter().find(|(name, _)| name == key).map(|(_, value)| value.clone());
+    // The players follow a second padding, which is split into its own strings here
+    let players = strings
+        .skip_while(|text| text != "\u{1}player_")
+        .skip(2)
+        .take_while(|name| !name.is_empty())
+        .collect();
+    Some(QueryStatus {
+        motd: value("hostname")?,
+        game_type: value("gametype").unwrap_or_default(),
+        map: value("map").unwrap_or_default(),
+        online: value("numplayers")?.parse().ok()?,
+        max: value("maxplayers")?.parse().ok()?,
+        host_port: value("hostport").and_then(|port| port.parse().ok()).unwrap_or(0),
+        host_ip: value("hostip").unwrap_or_default(),
+        version: value("version"),
+        plugins: value("plugins"),
+        players,
+    })
+}
+
+fn encode_basic_stat(status: &QueryStatus) -> Vec<u8> {
+    let mut data = Vec::new();
+    for text in [&status.motd, &status.game_type, &status.map, &status.online.to_string(), &status.max.to_string()] {
+        data.extend_from_slice(text.as_bytes());
+        data.push(0);
+    }
+    data.extend_from_slice(&status.host_port.to_le_bytes());
+    data.extend_from_slice(status.host_ip.as_bytes());
+    data.push(0);
+    data
+}
+
+fn encode_full_stat(status: &QueryStatus) -> Vec<u8> {
+    let mut data = QUERY_PADDING.to_vec();
+    let values = [
+        ("hostname", status.motd.clone()),
+        ("gametype", status.game_type.clone()),
+        ("game_id", "MINECRAFT".to_string()),
+        ("version", status.version.clone().unwrap_or_default()),
+        ("plugins", status.plugins.clone().unwrap_or_default()),
+        ("map", status.map.clone()),
+        ("numplayers", status.online.to_string()),
+        ("maxplayers", status.max.to_string()),
+        ("hostport", status.host_port.to_string()),
+        ("hostip", status.host_ip.clone()),
+    ];
+    for (key, value) in values.iter() {
+        data.extend_from_slice(key.as_bytes());
+        data.push(0);
+        data.extend_from_slice(value.as_bytes());
+        data.push(0);
+    }
+    data.push(0);
+    data.extend_from_slice(b"\x01player_\x00\x00");
+    for player in &status.players {
+        data.extend_from_slice(player.as_bytes());
+        data.push(0);
+    }
+    data.push(0);
+    data
+}
+
+/// Answers a single Query request, handshakes are given a token for their address that stat requests must include.
+fn answer_query(request: &[u8], from: IpAddr, tokens: &mut Vec<(IpAddr, i32, Instant)>, status: &QueryStatus) -> Option<Vec<u8>> {
+    if request.len() < 7 || request[..2] != [0xFE, 0xFD] {
+        return None;
+    }
+    let kind = request[2];
+    let session = &request[3..7];
+    tokens.retain(|(_, _, issued)| issued.elapsed() < QUERY_TOKEN_LIFETIME);
+    let mut response = vec![kind];
+    response.extend_from_slice(session);
+    match kind {
+        QUERY_HANDSHAKE => {
+            let token = match tokens.iter().find(|(address, _, _)| *address == from) {
+                Some((_, token, _)) => *token,
+                None => {
+                    let token = (SystemTime::now().duration_since(UNIX_EPOCH).ok()?.subsec_nanos() & 0x7FFFFFFF) as i32;
+                    tokens.push((from, token, Instant::now()));
+                    token
+                }
+            };
+            response.extend_from_slice(token.to_string().as_bytes());
+            response.push(0);
+        }
+        QUERY_STAT if request.len() >= 11 => {
+            let token = i32::from_be_bytes([request[7], request[8], request[9], request[10]]);
+            if !tokens.iter().any(|(address, issued, _)| *address == from && *issued == token) {
+                return None;
+            }
+            // Full stats are asked for with four bytes of padding after the token
+            if request.len() >= 15 {
+                response.extend(encode_full_stat(status));
+            } else {
+                response.extend(encode_basic_stat(status));
+            }
+        }
+        _ => return None,
+    }
+    Some(response)
+}
+
+/// Whether the monitor should answer Query for the server, only until the server could start its own listener.
+fn query_responder_active(data: &shared_data::ServerSharedData) -> bool {
+    match *data.mcserver_state.lock().unwrap() {
+        MinecraftServerState::Off | MinecraftServerState::Eula => true,
+        // The server starts listening once its world is loaded, the port is handed back before then
+        MinecraftServerState::Starting => data.startup.lock().unwrap().stage < StartupStage::PreparingLevel,
+        MinecraftServerState::Running => false,
+    }
+}
+
+/// The most players the server allows, from its last `list` or otherwise `server.properties`.
+fn max_players(data: &shared_data::ServerSharedData) -> u32 {
+    match *data.max_player_count.lock().unwrap() {
+        0 => server_property("max-players").and_then(|max| max.parse().ok()).unwrap_or(20),
+        max => max,
+    }
+}
+
+/// What the monitor reports through Query while the server is down, from `server.properties` and the shared data.
+fn down_query_status(data: &shared_data::ServerSharedData) -> QueryStatus {
+    let motd = server_property("motd").unwrap_or_else(|| "A Minecraft Server".to_string());
+    QueryStatus {
+        motd: format!("{} (restarting)", strip_formatting(&motd)),
+        game_type: "SMP".to_string(),
+        map: server_property("level-name").unwrap_or_else(|| "world".to_string()),
+        online: 0,
+        max: max_players(data),
+        host_port: game_port(),
+        host_ip: server_property("server-ip").filter(|ip| !ip.is_empty()).unwrap_or_else(|| "0.0.0.0".to_string()),
+        version: Some(String::new()),
+        plugins: Some(String::new()),
+        players: Vec::new(),
+    }
+}
+
+/// Answers Query requests while the server is down or starting, until the monitor shuts down.
+///
+/// The port is only held while the server can not be using it, once the server is about to start its own listener
+/// the port is released and it is taken again after the server stops.
+pub fn run_query_responder(data: shared_data::ServerSharedData) {
+    let mut socket: Option<UdpSocket> = None;
+    let mut tokens = Vec::new();
+    let mut buffer = [0; 1500];
+    loop {
+        if *data.gen_state.lock().unwrap() == GeneralState::ShutDown {
+            break;
+        }
+        if !query_responder_active(&data) {
+            socket = None;
+            thread::sleep(Duration::from_millis(250));
+            continue;
+        }
+        let listening = match &socket {
+            Some(socket) => socket,
+            None => match UdpSocket::bind(("0.0.0.0", query_port())) {
+                Ok(bound) => {
+                    let _ = bound.set_read_timeout(Some(Duration::from_millis(250)));
+                    socket.insert(bound)
+                }
+                // The server might still be letting go of the port after stopping
+                Err(_) => {
+                    thread::sleep(Duration::from_secs(1));
+                    continue;
+                }
+            },
+        };
+        if let Ok((length, from)) = listening.recv_from(&mut buffer) {
+            if let Some(response) = answer_query(&buffer[..length], from.ip(), &mut tokens, &down_query_status(&data)) {
+                let _ = listening.send_to(&response, from);
+            }
+        }
+    }
+}
+
+/// Whether the placeholder should hold the game port, only while the server is down and no process could be using it.
+fn placeholder_active(data: &shared_data::ServerSharedData) -> bool {
+    let state = *data.mcserver_state.lock().unwrap();
+    if state == MinecraftServerState::Running {
+        return false;
+    }
+    // A server that has printed that it is stopping can still be holding the port until it exits
+    match data.child.lock().unwrap().as_mut() {
+        Some(child) => matches!(child.try_wait(), Ok(Some(_))),
+        None => true,
+    }
+}
+
+/// An estimate of how long until the server is back, from how long previous starts took.
+fn placeholder_eta(data: &shared_data::ServerSharedData) -> String {
+    let history = data.startup_history.lock().unwrap();
+    if history.is_empty() {
+        return "a moment".to_string();
+    }
+    let mut remaining = history.iter().map(|record| record.duration).sum::<f64>() / history.len() as f64;
+    if *data.mcserver_state.lock().unwrap() == MinecraftServerState::Starting {
+        remaining -= data.startup.lock().unwrap().started.elapsed().as_secs_f64();
+    }
+    match remaining.round() as i64 {
+        seconds if seconds > 0 => format!("~{}s", seconds),
+        _ => "a moment".to_string(),
+    }
+}
+
+/// Answers a single connection to the placeholder, a status request is given the placeholder's MOTD and a login is
+/// disconnected with its message.
+fn answer_placeholder(mut stream: TcpStream, motd: &str, message: &str, max: u32) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(PLACEHOLDER_TIMEOUT))?;
+    stream.set_write_timeout(Some(PLACEHOLDER_TIMEOUT))?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "The client did not send a handshake");
+    let handshake = read_packet(&mut stream)?;
+    let mut handshake = handshake.as_slice();
+    if read_varint(&mut handshake)? != 0x00 {
+        return Err(invalid());
+    }
+    let protocol = read_varint(&mut handshake)?;
+    // The address and port that the client connected to are not needed
+    let address = read_varint(&mut handshake)?.max(0) as usize;
+    let next = handshake.get(address + 2).ok_or_else(invalid)?;
+    let mut response = Vec::new();
+    write_varint(&mut response, 0x00);
+    if *next == 1 {
+        read_packet(&mut stream)?;
+        // The client's own protocol is given back so that the placeholder is not shown as an incompatible version
+        write_string(
+            &mut response,
+            &format!(
+                "{{\"version\": {{\"name\": \"Restarting\", \"protocol\": {}}}, \"players\": {{\"max\": {}, \"online\": 0}}, \"description\": {{\"text\": \"{}\"}}}}",
+                protocol,
+                max,
+                json_escape(motd)
+            ),
+        );
+        write_packet(&mut stream, &response)?;
+        let ping = read_packet(&mut stream)?;
+        write_packet(&mut stream, &ping)
+    } else {
+        read_packet(&mut stream)?;
+        write_string(&mut response, &format!("{{\"text\": \"{}\"}}", json_escape(message)));
+        write_packet(&mut stream, &response)
+    }
+}
+
+/// Holds the game port whenever no server process is alive, such as between launches, during a crash backoff or once a
+/// server waiting for the EULA has exited, until the monitor shuts down.
+///
+/// The listener is kept in the shared data so that a launch can take it, releasing the port, before the server's
+/// process is started. Nothing answers on the port from then until the process exits, including while it starts.
+pub fn run_placeholder(data: shared_data::ServerSharedData, config: Configuration) {
+    let active = Arc::new(AtomicUsize::new(0));
+    loop {
+        if *data.gen_state.lock().unwrap() == GeneralState::ShutDown {
+            *data.placeholder.lock().unwrap() = None;
+            break;
+        }
+        {
+            let mut placeholder = data.placeholder.lock().unwrap();
+            if !placeholder_active(&data) {
+                *placeholder = None;
+            } else if placeholder.is_none() {
+                let address = server_property("server-ip").filter(|ip| !ip.is_empty());
+                match TcpListener::bind((address.as_deref().unwrap_or("0.0.0.0"), game_port())) {
+                    Ok(listener) if listener.set_nonblocking(true).is_ok() => *placeholder = Some(listener),
+                    // Another process still has the port, it is tried again on the next loop
+                    _ => {}
+                }
+            }
+            if let Some(listener) = placeholder.as_ref() {
+                while let Ok((stream, _)) = listener.accept() {
+                    // The game port is public, so clients are capped like those of the web server and RCON
+                    if active.load(Ordering::SeqCst) >= MAX_PLACEHOLDER_CLIENTS {
+                        continue;
+                    }
+                    let slot = RequestSlot::take(&active);
+                    let eta = placeholder_eta(&data);
+                    let motd = config.placeholder_motd.replace("{eta}", &eta);
+                    let message = config.placeholder_message.replace("{eta}", &eta);
+                    let max = max_players(&data);
+                    thread::spawn(move || {
+                        let _slot = slot;
+                        let _ = answer_placeholder(stream, &motd, &message, max);
+                    });
+                }
+            }
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholder_answers_pings_and_logins() {
+        use std::net::TcpListener;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let placeholder = thread::spawn(move || {
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                answer_placeholder(stream, "Restarting, back in ~30s", "Try again in \"~30s\"", 20).unwrap();
+            }
+        });
+        let status = server_list_ping("127.0.0.1", port, Duration::from_secs(5)).unwrap();
+        assert_eq!((status.version.as_str(), status.protocol), ("Restarting", -1));
+        assert_eq!((status.online, status.max), (0, 20));
+        assert_eq!(status.motd, "Restarting, back in ~30s");
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let mut handshake = Vec::new();
+        write_varint(&mut handshake, 0x00);
+        write_varint(&mut handshake, 765);
+        write_string(&mut handshake, "localhost");
+        handshake.extend_from_slice(&port.to_be_bytes());
+        write_varint(&mut handshake, 2);
+        write_packet(&mut stream, &handshake).unwrap();
+        let mut login = Vec::new();
+        write_varint(&mut login, 0x00);
+        write_string(&mut login, "Notch");
+        write_packet(&mut stream, &login).unwrap();
+        let disconnect = read_packet(&mut stream).unwrap();
+        let mut disconnect = disconnect.as_slice();
+        assert_eq!(read_varint(&mut disconnect).unwrap(), 0x00);
+        let reason = Json::parse(&String::from_utf8_lossy(&disconnect[1..])).unwrap();
+        assert_eq!(reason.get("text").and_then(Json::as_str), Some("Try again in \"~30s\""));
+        placeholder.join().unwrap();
+    }
+
+    #[test]
+    fn query_against_the_responder() {
+        let status = QueryStatus {
+            motd: "A Minecraft Server (restarting)".to_string(),
+            game_type: "SMP".to_string(),
+            map: "world".to_string(),
+            online: 2,
+            max: 20,
+            host_port: 25565,
+            host_ip: "0.0.0.0".to_string(),
+            version: Some("1.20.4".to_string()),
+            plugins: Some("Paper on 1.20.4: WorldEdit 7.2.15".to_string()),
+            players: vec!["Notch".to_string(), "jeb_".to_string()],
+        };
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let responder_status = status.clone();
+        let responder = thread::spawn(move || {
+            let mut tokens = Vec::new();
+            let mut buffer = [0; 1500];
+            for _ in 0..4 {
+                let (length, from) = socket.recv_from(&mut buffer).unwrap();
+                let response = answer_query(&buffer[..length], from.ip(), &mut tokens, &responder_status).unwrap();
+                socket.send_to(&response, from).unwrap();
+            }
+            // Stat requests without a token that was handed out are ignored
+            let request = [0xFE, 0xFD, 0x00, 0, 0, 0, 1, 0, 0, 0, 0];
+            assert_eq!(answer_query(&request, "10.0.0.1".parse().unwrap(), &mut tokens, &responder_status), None);
+        });
+        let basic = query_server("127.0.0.1", port, false, Duration::from_secs(5)).unwrap();
+        let full = query_server("127.0.0.1", port, true, Duration::from_secs(5)).unwrap();
+        responder.join().unwrap();
+        assert_eq!(basic, QueryStatus { version: None, plugins: None, players: Vec::new(), ..status.clone() });
+        assert_eq!(full, status);
+    }
+
+    #[test]
+    fn server_list_ping_against_a_fake_server() {
+        use std::net::TcpListener;
+        let status = r#"{"version": {"name": "Paper 1.20.4", "protocol": 765}, "players": {"max": 20, "online": 2,
+            "sample": [{"name": "Notch", "id": "069a79f4-44e9-4726-a5be-fca90e38aaf5"}, {"name": "jeb_", "id": "853c80ef-3c37-49fd-aa49-938b674adae6"}]},
+            "description": {"text": "§aA ", "extra": [{"text": "Minecraft Server ☃"}]}, "enforcesSecureChat": true}"#;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let handshake = read_packet(&mut stream).unwrap();
+            let mut handshake = handshake.as_slice();
+            assert_eq!(read_varint(&mut handshake).unwrap(), 0x00);
+            assert_eq!(read_varint(&mut handshake).unwrap(), -1);
+            assert_eq!(read_varint(&mut handshake).unwrap(), 9);
+            assert_eq!(*handshake.last().unwrap(), 1);
+            assert_eq!(read_packet(&mut stream).unwrap(), vec![0x00]);
+            let mut response = Vec::new();
+            write_varint(&mut response, 0x00);
+            write_string(&mut response, status);
+            write_packet(&mut stream, &response).unwrap();
+            let ping = read_packet(&mut stream).unwrap();
+            assert_eq!(ping[0], 0x01);
+            write_packet(&mut stream, &ping).unwrap();
+        });
+        let status = server_list_ping("127.0.0.1", port, Duration::from_secs(5)).unwrap();
+        server.join().unwrap();
+        assert_eq!(status.version, "Paper 1.20.4");
+        assert_eq!(status.protocol, 765);
+        assert_eq!((status.online, status.max), (2, 20));
+        assert_eq!(status.sample, vec!["Notch".to_string(), "jeb_".to_string()]);
+        assert_eq!(status.motd, "A Minecraft Server ☃");
+        assert!(status.latency.is_some());
+
+        let mut buffer = Vec::new();
+        write_varint(&mut buffer, 25565);
+        assert_eq!(buffer, vec![0xdd, 0xc7, 0x01]);
+        assert_eq!(read_varint(&mut buffer.as_slice()).unwrap(), 25565);
+    }
+}
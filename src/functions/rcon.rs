@@ -0,0 +1,374 @@
+//! The Source RCON protocol that Minecraft servers offer when `enable-rcon` is set in `server.properties`.
+//!
+//! Each packet is its length, an id, a type and a body ending in two null bytes, all numbers are little endian.
+//! Unlike writing to the server's console, every command gets its own response. The monitor can also listen for RCON
+//! itself, so that RCON clients work whether or not the server's own RCON is enabled.
+//!
+//! The monitor's RCON has a single shared `rcon_password` that is sent in plain text, and any client that knows it can
+//! run every console command. There are no user accounts or per command permissions.
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use super::configuration::Configuration;
+use super::minecraft_related::{line_message, server_property};
+use super::server_interactions::{command_response, CommandResponse};
+use super::shared_data::{self, ConsoleCommand, GeneralState, MinecraftServerState};
+use super::web_server::RequestSlot;
+
+/// Sent by the client to log in with the password.
+pub const SERVERDATA_AUTH: i32 = 3;
+/// Sent by the client to run a command, and by the server in answer to a login.
+pub const SERVERDATA_EXECCOMMAND: i32 = 2;
+pub const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+/// Sent by the server with the response to a command.
+pub const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+/// The port that RCON listens on when `rcon.port` is not set.
+const DEFAULT_RCON_PORT: u16 = 25575;
+
+/// The largest packet that is read, servers split responses into packets of at most 4096 bytes.
+const MAX_PACKET: i32 = 1 << 16;
+
+/// A single RCON packet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RconPacket {
+    pub id: i32,
+    pub kind: i32,
+    pub body: String,
+}
+
+impl RconPacket {
+    pub fn new(id: i32, kind: i32, body: &str) -> RconPacket {
+        RconPacket {
+            id,
+            kind,
+            body: body.to_string(),
+        }
+    }
+}
+
+/// Writes a packet with its length in front.
+pub fn write_packet(stream: &mut impl Write, packet: &RconPacket) -> io::Result<()> {
+    let mut data = Vec::with_capacity(packet.body.len() + 14);
+    data.extend_from_slice(&(packet.body.len() as i32 + 10).to_le_bytes());
+    data.extend_from_slice(&packet.id.to_le_bytes());
+    data.extend_from_slice(&packet.kind.to_le_bytes());
+    data.extend_from_slice(packet.body.as_bytes());
+    data.extend_from_slice(&[0, 0]);
+    stream.write_all(&data)
+}
+
+pub fn read_packet(stream: &mut impl Read) -> io::Result<RconPacket> {
+    let mut number = [0; 4];
+    stream.read_exact(&mut number)?;
+    let length = i32::from_le_bytes(number);
+    if !(10..=MAX_PACKET).contains(&length) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "The packet length is not valid"));
+    }
+    let mut data = vec![0; length as usize];
+    stream.read_exact(&mut data)?;
+    let body = &data[8..data.len() - 2];
+    Ok(RconPacket {
+        id: i32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+        kind: i32::from_le_bytes([data[4], data[5], data[6], data[7]]),
+        body: String::from_utf8_lossy(body).to_string(),
+    })
+}
+
+/// A logged in connection to a server's RCON.
+pub struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl RconClient {
+    /// Connects and logs in, a wrong password is returned as a `PermissionDenied` error.
+    pub fn connect(host: &str, port: u16, password: &str, timeout: Duration) -> io::Result<RconClient> {
+        let address = (host, port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "The address could not be resolved"))?;
+        let stream = TcpStream::connect_timeout(&address, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        let mut client = RconClient { stream, next_id: 1 };
+        let id = client.next_id();
+        write_packet(&mut client.stream, &RconPacket::new(id, SERVERDATA_AUTH, password))?;
+        // Source servers send an empty response before the result of the login, Minecraft does not
+        loop {
+            let packet = read_packet(&mut client.stream)?;
+            if packet.kind != SERVERDATA_AUTH_RESPONSE {
+                continue;
+            }
+            if packet.id == -1 {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "The RCON password was not accepted"));
+            }
+            return Ok(client);
+        }
+    }
+
+    /// Runs a command and returns its response.
+    ///
+    /// Long responses are split over several packets, a second packet that the server does not understand is sent
+    /// after the command and everything received before its answer is part of the response.
+    pub fn command(&mut self, command: &str) -> io::Result<String> {
+        let id = self.next_id();
+        let marker = self.next_id();
+        write_packet(&mut self.stream, &RconPacket::new(id, SERVERDATA_EXECCOMMAND, command))?;
+        write_packet(&mut self.stream, &RconPacket::new(marker, SERVERDATA_RESPONSE_VALUE, ""))?;
+        let mut response = String::new();
+        loop {
+            let packet = read_packet(&mut self.stream)?;
+            if packet.id == marker {
+                return Ok(response);
+            }
+            if packet.id == id {
+                response.push_str(&packet.body);
+            }
+        }
+    }
+
+    fn next_id(&mut self) -> i32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        id
+    }
+}
+
+/// The longest that the monitor's RCON waits for the output of a command.
+const RESPONSE_WAIT: Duration = Duration::from_millis(1000);
+
+/// The longest body of a single response packet, longer responses are split.
+const RESPONSE_PACKET: usize = 4096;
+
+/// The most clients that are connected at once, any more are disconnected straight away.
+const MAX_CLIENTS: usize = 8;
+
+/// How long a client can stay connected without sending a packet.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Accepts RCON connections for the monitor itself until the monitor shuts down.
+///
+/// Commands are sent to the server through `web_sender`, like those from the web API, and answered with the output
+/// that they produced. Anyone with the `rcon_password` can run any command.
+pub fn run_rcon_server(
+    data: shared_data::ServerSharedData,
+    web_sender: Sender<ConsoleCommand>,
+    config: Configuration,
+) -> io::Result<()> {
+    let listener = TcpListener::bind((config.address, config.rcon_port))?;
+    // Shutdowns have to be noticed while waiting for a connection
+    listener.set_nonblocking(true)?;
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        if *data.gen_state.lock().unwrap() == GeneralState::ShutDown {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if active.load(Ordering::SeqCst) >= MAX_CLIENTS {
+            continue;
+        }
+        stream.set_nonblocking(false)?;
+        let slot = RequestSlot::take(&active);
+        let data = data.clone();
+        let web_sender = web_sender.clone();
+        let password = config.rcon_password.clone();
+        // Clients stay connected between commands and across restarts, each is handled on its own
+        thread::spawn(move || {
+            let _slot = slot;
+            let _ = handle_rcon_client(stream, &data, &web_sender, &password);
+        });
+    }
+    Ok(())
+}
+
+fn handle_rcon_client(
+    mut stream: TcpStream,
+    data: &shared_data::ServerSharedData,
+    web_sender: &Sender<ConsoleCommand>,
+    password: &str,
+) -> io::Result<()> {
+    // Clients that go quiet are disconnected, so they can't hold one of the `MAX_CLIENTS` places forever
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let login = read_packet(&mut stream)?;
+    if login.kind != SERVERDATA_AUTH || login.body != password {
+        return write_packet(&mut stream, &RconPacket::new(-1, SERVERDATA_AUTH_RESPONSE, ""));
+    }
+    write_packet(&mut stream, &RconPacket::new(login.id, SERVERDATA_AUTH_RESPONSE, ""))?;
+    loop {
+        let packet = read_packet(&mut stream)?;
+        if packet.kind != SERVERDATA_EXECCOMMAND {
+            // Answered like Minecraft does, clients use this to find the end of a long response
+            let unknown = format!("Unknown request {:x}", packet.kind);
+            write_packet(&mut stream, &RconPacket::new(packet.id, SERVERDATA_RESPONSE_VALUE, &unknown))?;
+            continue;
+        }
+        // Between launches a command would wait for the next server, which may not answer it the same way
+        if *data.mcserver_state.lock().unwrap() != MinecraftServerState::Running {
+            let refused = "The server is not running";
+            write_packet(&mut stream, &RconPacket::new(packet.id, SERVERDATA_RESPONSE_VALUE, refused))?;
+            continue;
+        }
+        let response = match command_response(&packet.body, RESPONSE_WAIT, web_sender, &data.server_output) {
+            CommandResponse::Lines(lines) => lines
+                .iter()
+                .map(|line| line_message(line))
+                .collect::<Vec<&str>>()
+                .join("\n"),
+            CommandResponse::Queued => String::new(),
+            CommandResponse::Unsent => return Ok(()),
+        };
+        for body in response_packets(&response) {
+            write_packet(&mut stream, &RconPacket::new(packet.id, SERVERDATA_RESPONSE_VALUE, body))?;
+        }
+    }
+}
+
+/// Splits a response into the bodies of packets that are no longer than clients expect.
+fn response_packets(response: &str) -> Vec<&str> {
+    let mut packets = Vec::new();
+    let mut rest = response;
+    while rest.len() > RESPONSE_PACKET {
+        let mut end = RESPONSE_PACKET;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        packets.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    packets.push(rest);
+    packets
+}
+
+/// The port and password of the server's RCON, if it is enabled in `server.properties` with a password.
+pub fn rcon_settings() -> Option<(u16, String)> {
+    if server_property("enable-rcon")? != "true" {
+        return None;
+    }
+    let password = server_property("rcon.password").filter(|password| !password.is_empty())?;
+    let port = server_property("rcon.port")
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_RCON_PORT);
+    Some((port, password))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    /// Answers like a Minecraft server, `help` is answered over two packets.
+    fn mock_server(password: &'static str) -> (u16, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let login = read_packet(&mut stream).unwrap();
+            assert_eq!(login.kind, SERVERDATA_AUTH);
+            if login.body != password {
+                write_packet(&mut stream, &RconPacket::new(-1, SERVERDATA_AUTH_RESPONSE, "")).unwrap();
+                return;
+            }
+            write_packet(&mut stream, &RconPacket::new(login.id, SERVERDATA_AUTH_RESPONSE, "")).unwrap();
+            while let Ok(packet) = read_packet(&mut stream) {
+                let response = match (packet.kind, packet.body.as_str()) {
+                    (SERVERDATA_EXECCOMMAND, "help") => {
+                        write_packet(&mut stream, &RconPacket::new(packet.id, SERVERDATA_RESPONSE_VALUE, "/advancement ...")).unwrap();
+                        "/ban ...".to_string()
+                    }
+                    (SERVERDATA_EXECCOMMAND, "list") => "There are 0 of a max of 20 players online: ".to_string(),
+                    (kind, _) => format!("Unknown request {:x}", kind),
+                };
+                write_packet(&mut stream, &RconPacket::new(packet.id, SERVERDATA_RESPONSE_VALUE, &response)).unwrap();
+            }
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn rcon_client() {
+        let mut buffer = Vec::new();
+        write_packet(&mut buffer, &RconPacket::new(7, SERVERDATA_EXECCOMMAND, "list")).unwrap();
+        assert_eq!(buffer, vec![14, 0, 0, 0, 7, 0, 0, 0, 2, 0, 0, 0, b'l', b'i', b's', b't', 0, 0]);
+        assert_eq!(read_packet(&mut buffer.as_slice()).unwrap(), RconPacket::new(7, SERVERDATA_EXECCOMMAND, "list"));
+
+        let (port, server) = mock_server("secret");
+        let mut client = RconClient::connect("127.0.0.1", port, "secret", Duration::from_secs(5)).unwrap();
+        assert_eq!(client.command("list").unwrap(), "There are 0 of a max of 20 players online: ");
+        assert_eq!(client.command("help").unwrap(), "/advancement .../ban ...");
+        drop(client);
+        server.join().unwrap();
+
+        let (port, server) = mock_server("secret");
+        let refused = RconClient::connect("127.0.0.1", port, "guess", Duration::from_secs(5));
+        assert_eq!(refused.err().map(|error| error.kind()), Some(io::ErrorKind::PermissionDenied));
+        server.join().unwrap();
+
+        let long = "é".repeat(RESPONSE_PACKET);
+        let packets = response_packets(&long);
+        assert_eq!(packets.len(), 2);
+        assert!(packets.iter().all(|packet| packet.len() <= RESPONSE_PACKET));
+        assert_eq!(packets.concat(), long);
+        assert_eq!(response_packets(""), vec![""]);
+    }
+
+    /// Runs the monitor's side of one RCON connection and returns a stream connected to it.
+    fn monitor_client(data: &shared_data::ServerSharedData, sender: Sender<ConsoleCommand>) -> (TcpStream, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let data = data.clone();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let _ = handle_rcon_client(stream, &data, &sender, "secret");
+        });
+        let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        (stream, handle)
+    }
+
+    #[test]
+    fn rcon_server() {
+        let data = shared_data::ServerSharedData::new();
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        let (mut stream, client) = monitor_client(&data, sender.clone());
+        write_packet(&mut stream, &RconPacket::new(4, SERVERDATA_AUTH, "guess")).unwrap();
+        assert_eq!(read_packet(&mut stream).unwrap(), RconPacket::new(-1, SERVERDATA_AUTH_RESPONSE, ""));
+        client.join().unwrap();
+
+        let (mut stream, client) = monitor_client(&data, sender);
+        write_packet(&mut stream, &RconPacket::new(4, SERVERDATA_AUTH, "secret")).unwrap();
+        assert_eq!(read_packet(&mut stream).unwrap(), RconPacket::new(4, SERVERDATA_AUTH_RESPONSE, ""));
+        write_packet(&mut stream, &RconPacket::new(5, SERVERDATA_RESPONSE_VALUE, "")).unwrap();
+        assert_eq!(
+            read_packet(&mut stream).unwrap(),
+            RconPacket::new(5, SERVERDATA_RESPONSE_VALUE, "Unknown request 0")
+        );
+        // A server that is still starting can not run it, so the command is refused instead of being queued
+        write_packet(&mut stream, &RconPacket::new(6, SERVERDATA_EXECCOMMAND, "list")).unwrap();
+        assert_eq!(
+            read_packet(&mut stream).unwrap(),
+            RconPacket::new(6, SERVERDATA_RESPONSE_VALUE, "The server is not running")
+        );
+        assert!(receiver.try_recv().is_err());
+        drop(stream);
+        client.join().unwrap();
+    }
+}
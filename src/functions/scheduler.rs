@@ -0,0 +1,529 @@
+//! Runs console commands, restarts, backups and announcements on cron-like schedules.
+//!
+//! Schedules use the five fields of cron, `minute hour day-of-month month day-of-week`, followed by an action:
+//! `command <command>`, `announce <message>`, `restart [delay]` or `backup`. Times are in the local time of the host.
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+    sync::mpsc::Sender,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::configuration::Configuration;
+use super::minecraft_related::{
+    lag_tps, record_performance, save_player_history, Capability, PLAYER_HISTORY_INTERVAL,
+};
+use super::server_interactions::{self, CommandResponse};
+use super::system_metrics::{world_directory, SystemSampler, SAMPLE_INTERVAL, SYSTEM_HISTORY};
+use super::shared_data::{
+    self, ConsoleCommand, GeneralState, JobAction, MinecraftServerState, PerformanceSource,
+};
+
+/// Tasks that are due are only looked for up to a year ahead.
+const SEARCH_MINUTES: i64 = 366 * 24 * 60;
+
+/// The values that each field of a cron expression matches, stored as bit sets.
+#[derive(Clone)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Cron matches either of the day fields when both of them are restricted.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Schedule, String> {
+        let fields = expression.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 5 {
+            return Err(format!(
+                "Expected five fields in the schedule, found: {}",
+                expression
+            ));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // Both 0 and 7 are Sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Schedule {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2].starts_with('*'),
+            any_weekday: fields[4].starts_with('*'),
+        })
+    }
+
+    pub fn matches(&self, time: &LocalTime) -> bool {
+        let day = self.days & (1 << time.day) != 0;
+        let weekday = self.weekdays & (1 << time.weekday) != 0;
+        let day_matches = if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        };
+        self.minutes & (1 << time.minute) != 0
+            && self.hours & (1 << time.hour) != 0
+            && self.months & (1 << time.month) != 0
+            && day_matches
+    }
+
+    /// Finds the first local minute after the given one that the schedule matches.
+    pub fn next_after(&self, minute: i64) -> Option<i64> {
+        (minute + 1..minute + SEARCH_MINUTES).find(|minute| self.matches(&LocalTime::from_minute(*minute)))
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut values = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(loc) => match part[loc + 1..].parse::<u32>() {
+                Ok(step) if step > 0 => (&part[..loc], Some(step)),
+                _ => return Err(format!("Invalid step in schedule, found: {}", part)),
+            },
+            None => (part, None),
+        };
+        let parse = |value: &str| match value.parse::<u32>() {
+            Ok(value) if value >= min && value <= max => Ok(value),
+            _ => Err(format!(
+                "Invalid value in schedule, expected {} to {}, found: {}",
+                min, max, value
+            )),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some(loc) = range.find('-') {
+            (parse(&range[..loc])?, parse(&range[loc + 1..])?)
+        } else if step.is_some() {
+            // A single value with a step, such as 5/15, runs from the value to the end of the range
+            (parse(range)?, max)
+        } else {
+            let value = parse(range)?;
+            (value, value)
+        };
+        if start > end {
+            return Err(format!("Invalid range in schedule, found: {}", part));
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            values |= 1 << value;
+        }
+    }
+    Ok(values)
+}
+
+/// A calendar time in the host's timezone.
+pub struct LocalTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    /// Days since Sunday.
+    pub weekday: u32,
+}
+
+impl LocalTime {
+    /// Converts minutes since the Unix epoch, already shifted to local time, into a calendar time.
+    pub fn from_minute(minute: i64) -> LocalTime {
+        let days = minute.div_euclid(24 * 60);
+        let minute_of_day = minute.rem_euclid(24 * 60);
+        // Days to civil date from http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        LocalTime {
+            year: yoe + era * 400 + if month <= 2 { 1 } else { 0 },
+            month: month as u32,
+            day: day as u32,
+            hour: (minute_of_day / 60) as u32,
+            minute: (minute_of_day % 60) as u32,
+            // The epoch was on a Thursday
+            weekday: (days + 4).rem_euclid(7) as u32,
+        }
+    }
+}
+
+/// Seconds since the Unix epoch.
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// The host's current offset from UTC in seconds, read from the date command as std has no timezone support.
+///
+/// The offset is read again on every call so that changes to and from daylight saving time are followed.
+pub fn local_offset() -> i64 {
+    let output = match Command::new("date").arg("+%z").output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        Err(_) => return 0, // Windows does not have a compatible date command, use UTC
+    };
+    if output.len() != 5 {
+        return 0;
+    }
+    let hours = output[1..3].parse::<i64>().unwrap_or(0);
+    let minutes = output[3..5].parse::<i64>().unwrap_or(0);
+    let offset = hours * 3600 + minutes * 60;
+    if output.starts_with('-') {
+        -offset
+    } else {
+        offset
+    }
+}
+
+/// Minutes since the Unix epoch in local time.
+pub fn local_minute(unix_time: u64) -> i64 {
+    (unix_time as i64 + local_offset()).div_euclid(60)
+}
+
+/// Formats a time as `YYYY-MM-DD HH:MM` in local time.
+pub fn format_time(unix_time: u64) -> String {
+    let time = LocalTime::from_minute(local_minute(unix_time));
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        time.year, time.month, time.day, time.hour, time.minute
+    )
+}
+
+#[derive(Clone)]
+pub enum ScheduledAction {
+    Command(String),
+    Announce(String),
+    /// A graceful restart with the number of seconds that players are warned for.
+    Restart(u64),
+    Backup,
+}
+
+impl ScheduledAction {
+    pub fn parse(action: &str) -> Result<ScheduledAction, String> {
+        let (name, rest) = match action.find(' ') {
+            Some(loc) => (&action[..loc], action[loc + 1..].trim()),
+            None => (action, ""),
+        };
+        match name {
+            "command" if !rest.is_empty() => Ok(ScheduledAction::Command(rest.to_string())),
+            "announce" if !rest.is_empty() => Ok(ScheduledAction::Announce(rest.to_string())),
+            "restart" if rest.is_empty() => Ok(ScheduledAction::Restart(0)),
+            "restart" => match rest.parse::<u64>() {
+                Ok(delay) => Ok(ScheduledAction::Restart(delay)),
+                Err(_) => Err(format!("Invalid delay for a scheduled restart, found: {}", rest)),
+            },
+            "backup" => Ok(ScheduledAction::Backup),
+            _ => Err(format!(
+                "Unknown scheduled action, expected command, announce, restart or backup, found: {}",
+                action
+            )),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            ScheduledAction::Command(command) => format!("command {}", command),
+            ScheduledAction::Announce(message) => format!("announce {}", message),
+            ScheduledAction::Restart(delay) => format!("restart {}", delay),
+            ScheduledAction::Backup => "backup".to_string(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ScheduledTask {
+    pub id: u32,
+    pub expression: String,
+    pub schedule: Schedule,
+    pub action: ScheduledAction,
+    pub paused: bool,
+    pub last_run: Option<u64>,
+    pub last_result: Option<String>,
+    /// The local minute the task last ran in, so that it does not run twice when the scheduler restarts.
+    last_minute: Option<i64>,
+}
+
+impl ScheduledTask {
+    /// Parses a schedule followed by an action, such as `0 4 * * * restart 300`.
+    pub fn parse(id: u32, line: &str) -> Result<ScheduledTask, String> {
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() < 6 {
+            return Err(format!(
+                "Expected a schedule followed by an action, found: {}",
+                line
+            ));
+        }
+        let expression = fields[..5].join(" ");
+        Ok(ScheduledTask {
+            id,
+            schedule: Schedule::parse(&expression)?,
+            expression,
+            action: ScheduledAction::parse(&fields[5..].join(" "))?,
+            paused: false,
+            last_run: None,
+            last_result: None,
+            last_minute: None,
+        })
+    }
+
+    /// The next time, in seconds since the Unix epoch, that the task will run.
+    pub fn next_run(&self) -> Option<u64> {
+        if self.paused {
+            return None;
+        }
+        let offset = local_offset();
+        self.schedule
+            .next_after((unix_time() as i64 + offset).div_euclid(60))
+            .map(|minute| (minute * 60 - offset) as u64)
+    }
+}
+
+/// Checks every second for tasks that are due, exits with the other threads when the server restarts or shuts down.
+pub fn run_scheduler(
+    data: shared_data::ServerSharedData,
+    web_sender: Sender<ConsoleCommand>,
+    config: Configuration,
+) {
+    let mut last_checked = None;
+    let mut last_list = Instant::now();
+    let mut last_performance = Instant::now();
+    let mut last_skipped = data.lag.lock().unwrap().skipped_ticks;
+    let mut sampler = SystemSampler::new();
+    let mut last_sample: Option<Instant> = None;
+    let mut last_history_save = Instant::now();
+    loop {
+        {
+            let state = data.gen_state.lock().unwrap();
+            if *state == GeneralState::ShutDown || *state == GeneralState::Restart {
+                break;
+            }
+        }
+        data.heartbeats.lock().unwrap().scheduler = Some(Instant::now());
+        let now = unix_time();
+        // Offsets are whole minutes, so the local minute only changes along with the minute in UTC
+        if last_checked != Some(now / 60) {
+            last_checked = Some(now / 60);
+            let minute = local_minute(now);
+            let time = LocalTime::from_minute(minute);
+            let mut due = Vec::new();
+            for task in data.schedule.lock().unwrap().iter_mut() {
+                if !task.paused && task.last_minute != Some(minute) && task.schedule.matches(&time) {
+                    task.last_minute = Some(minute);
+                    task.last_run = Some(now);
+                    due.push((task.id, task.action.clone()));
+                }
+            }
+            for (id, action) in due {
+                let data = data.clone();
+                let web_sender = web_sender.clone();
+                let config = config.clone();
+                // Tasks such as backups can take a while, they should not hold up the others
+                thread::spawn(move || {
+                    let result = run_task(action, &data, web_sender, &config);
+                    if let Some(task) = data.schedule.lock().unwrap().iter_mut().find(|task| task.id == id) {
+                        task.last_result = Some(result);
+                    }
+                });
+            }
+        }
+        // Joins and leaves can be missed, the players online are corrected from the server's own list
+        if config.list_interval > 0 && last_list.elapsed() >= Duration::from_secs(config.list_interval) {
+            last_list = Instant::now();
+            if *data.mcserver_state.lock().unwrap() == MinecraftServerState::Running {
+                if let Some(list) = config.flavor.list_command() {
+                    let _ = web_sender.send(ConsoleCommand::new(list));
+                }
+            }
+        }
+        if config.performance_interval > 0
+            && last_performance.elapsed() >= Duration::from_secs(config.performance_interval)
+        {
+            let seconds = last_performance.elapsed().as_secs_f64();
+            last_performance = Instant::now();
+            let skipped = data.lag.lock().unwrap().skipped_ticks;
+            if *data.mcserver_state.lock().unwrap() == MinecraftServerState::Running {
+                let commands = config
+                    .flavor
+                    .capabilities()
+                    .iter()
+                    .filter(|capability| matches!(capability, Capability::Tps | Capability::Mspt | Capability::ForgeTps))
+                    .collect::<Vec<&Capability>>();
+                // The responses are read from the output, servers without a command are measured by their warnings.
+                // Vanilla's `debug stop` would give a measured rate but leaves a profiling report behind every time
+                if commands.is_empty() {
+                    let tps = lag_tps(skipped - last_skipped, seconds);
+                    record_performance(&data, Some(tps), None, PerformanceSource::Lag);
+                }
+                for capability in commands {
+                    let _ = web_sender.send(ConsoleCommand::new(capability.command()));
+                }
+            }
+            last_skipped = skipped;
+        }
+        if last_history_save.elapsed() >= PLAYER_HISTORY_INTERVAL {
+            last_history_save = Instant::now();
+            save_player_history(&data, true);
+        }
+        if last_sample.is_none_or(|last| last.elapsed() >= SAMPLE_INTERVAL) {
+            last_sample = Some(Instant::now());
+            let pid = data.child.lock().unwrap().as_ref().map(|child| child.id());
+            let sample = sampler.sample(pid, Path::new(&world_directory()));
+            let mut system = data.system.lock().unwrap();
+            system.push_back(sample);
+            if system.len() > SYSTEM_HISTORY {
+                system.pop_front();
+            }
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+fn run_task(
+    action: ScheduledAction,
+    data: &shared_data::ServerSharedData,
+    web_sender: Sender<ConsoleCommand>,
+    config: &Configuration,
+) -> String {
+    let running = *data.mcserver_state.lock().unwrap() == MinecraftServerState::Running;
+    match action {
+        ScheduledAction::Command(_) | ScheduledAction::Announce(_) if !running => {
+            "Skipped, the server is not running".to_string()
+        }
+        ScheduledAction::Command(command) => {
+            match server_interactions::command_response(
+                &command,
+                Duration::from_secs(2),
+                &web_sender,
+                &data.server_output,
+            ) {
+                CommandResponse::Unsent => "Failed to send the command".to_string(),
+                CommandResponse::Queued => "Sent".to_string(),
+                CommandResponse::Lines(lines) if lines.is_empty() => "Sent".to_string(),
+                CommandResponse::Lines(lines) => lines.join("\n"),
+            }
+        }
+        ScheduledAction::Announce(message) => {
+            match web_sender.send(ConsoleCommand::new(&format!("say {}", message))) {
+                Ok(_) => "Announced".to_string(),
+                Err(_) => "Failed to send the announcement".to_string(),
+            }
+        }
+        ScheduledAction::Restart(delay) => {
+            match server_interactions::begin_job(
+                JobAction::Restart,
+                delay,
+                Some("Scheduled restart".to_string()),
+                data.clone(),
+                web_sender,
+                config,
+            ) {
+                Ok(id) => format!("Started restart job {}", id),
+                Err(id) => format!("Skipped, job {} is already running", id),
+            }
+        }
+        ScheduledAction::Backup => match backup(data, &web_sender, config) {
+            Ok(archive) => format!("Created {}", archive),
+            Err(error) => format!("Backup failed: {}", error),
+        },
+    }
+}
+
+/// Archives every world in the server directory into the backup location.
+///
+/// While the server is running saving is turned off so that the world files do not change during the backup.
+pub fn backup(
+    data: &shared_data::ServerSharedData,
+    web_sender: &Sender<ConsoleCommand>,
+    config: &Configuration,
+) -> Result<String, String> {
+    let state = *data.mcserver_state.lock().unwrap();
+    if state == MinecraftServerState::Starting {
+        return Err("the server is starting and could be writing to the world".to_string());
+    }
+    let running = state == MinecraftServerState::Running;
+    if running {
+        let _ = web_sender.send(ConsoleCommand::new("save-off"));
+        if !server_interactions::save_all(data, web_sender, Duration::from_secs(config.stop_timeout)) {
+            let _ = web_sender.send(ConsoleCommand::new("save-on"));
+            return Err("the server did not finish saving".to_string());
+        }
+    }
+    let result = archive_worlds(&config.backup_location);
+    if running {
+        let _ = web_sender.send(ConsoleCommand::new("save-on"));
+    }
+    result
+}
+
+fn archive_worlds(backup_location: &str) -> Result<String, String> {
+    // Worlds are the directories with a level.dat, Bukkit based servers keep each dimension in its own world
+    let mut worlds = Vec::new();
+    for entry in fs::read_dir(".").map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.join("level.dat").exists() {
+            worlds.push(path);
+        }
+    }
+    if worlds.is_empty() {
+        return Err("no worlds were found in the server directory".to_string());
+    }
+    fs::create_dir_all(backup_location).map_err(|e| e.to_string())?;
+    let time = format_time(unix_time()).replace(" ", "_").replace(":", "-");
+    let archive = format!("{}/{}.tar.gz", backup_location, time);
+    if Path::new(&archive).exists() {
+        return Err(format!("{} already exists", archive));
+    }
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(&archive)
+        .args(&worlds)
+        .status()
+        .map_err(|e| format!("failed to run tar, {}", e))?;
+    if status.success() {
+        Ok(archive)
+    } else {
+        Err(format!("tar exited with {}", status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cron_expressions() {
+        // 2021-01-04 04:00 was a Monday
+        let monday = LocalTime::from_minute(26_828_880);
+        assert_eq!((monday.year, monday.month, monday.day), (2021, 1, 4));
+        assert_eq!((monday.hour, monday.minute, monday.weekday), (4, 0, 1));
+        assert!(Schedule::parse("0 4 * * *").unwrap().matches(&monday));
+        assert!(Schedule::parse("*/15 2-6 * * 1-5").unwrap().matches(&monday));
+        assert!(!Schedule::parse("0 4 * * 0,7").unwrap().matches(&monday));
+        // Either day field matches when both are restricted
+        assert!(Schedule::parse("0 4 1 * 1").unwrap().matches(&monday));
+        assert_eq!(
+            Schedule::parse("30 4 * * *").unwrap().next_after(26_828_880),
+            Some(26_828_910)
+        );
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("* * *").is_err());
+    }
+
+    #[test]
+    fn scheduled_tasks() {
+        let task = ScheduledTask::parse(1, "0 4 * * * restart 300").unwrap();
+        assert_eq!(task.expression, "0 4 * * *");
+        assert_eq!(task.action.describe(), "restart 300");
+        assert!(ScheduledTask::parse(2, "0 4 * * * explode").is_err());
+    }
+}
@@ -1,6 +1,34 @@
-use std::{collections::VecDeque, fs, sync::{mpsc::Sender, Arc, Mutex}, thread, time::Duration};
+use std::{
+    collections::VecDeque,
+    fs, io,
+    process::Command,
+    sync::{mpsc::Sender, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
 
-use super::shared_data::{self, GeneralState, MinecraftServerState};
+use super::configuration::Configuration;
+use super::log_parser::LogLevel;
+use super::player_lists::{add_entry, load_list, remove_entry, save_list, ListChange, ListError, PlayerList};
+use super::properties::{property_kind, validate_property, ServerProperties};
+use super::rcon::{rcon_settings, RconClient};
+use super::system_metrics::SystemSample;
+use super::scheduler::{format_time, unix_time, ScheduledTask};
+use super::json::{json_escape, Json};
+use super::minecraft_related::{command_response_patterns, is_command_response, line_message, server_property, ServerFlavor};
+use super::protocol::{game_port, query_port, query_server, server_list_ping};
+use super::shared_data::{
+    self, Alert, ChatMessage, ConsoleBuffer, LagStats, PerformanceSample, GameEvent, GameEventKind, PlayerDrift, PlayerRecord, ConsoleCommand, CrashReport, GeneralState, OutputStream, Job, JobAction, JobStage, MinecraftServerState,
+};
+
+/// The longest that a request to `/api/send` is allowed to wait for the server to respond.
+const MAX_COMMAND_WAIT: u64 = 10000;
+
+/// The number of shutdown and restart jobs that are remembered.
+const JOB_HISTORY: usize = 50;
+
+/// How long a restart job follows the next launch of the server before giving up on it.
+const RESTART_TIMEOUT: Duration = Duration::from_secs(600);
 
 /// Returns a String, in JSON format, of the current player data from the minecraft server
 ///
@@ -34,10 +62,7 @@ pub fn get_players(
         "{{\"playerCount\": \"{}\", \"playerCountMax\": \"{}\", \"players\": [",
         pc, pcm
     );
-    for player in p.iter() {
-        data.push_str((*player).as_str());
-        data.push_str(", ");
-    }
+    data.push_str(&json_names(&p));
     data.push_str("]}");
     format!(
         "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
@@ -45,32 +70,73 @@ pub fn get_players(
     )
 }
 
-pub fn get_console(chat: Arc<Mutex<VecDeque<(u32, String)>>>) -> String {
-    let chat = chat.lock().unwrap(); // FIXME try_lock?
-    let mut log = format!("{{\"chat\": {{\n");
-    for line in chat.iter() {
-        log.push_str(
+fn json_names(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| format!("\"{}\"", json_escape(name)))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// Returns a String, in JSON format, of the times that the players counted online differed from the server's `list`.
+pub fn get_player_drift(drift: Arc<Mutex<VecDeque<PlayerDrift>>>) -> String {
+    let drift = drift
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|drift| {
             format!(
-                "\"{}\":\"{}\",\n",
-                line.0,
-                line.1.replace("\n", "").replace("\"", "\\\"")
+                "{{\"time\": {}, \"tracked\": {}, \"listed\": {}, \"added\": [{}], \"removed\": [{}]}}",
+                json_time(Some(drift.time)),
+                drift.tracked,
+                drift.listed,
+                json_names(&drift.added),
+                json_names(&drift.removed)
             )
-            .as_str(),
-        );
-    }
-    log.push_str("}}");
+        })
+        .collect::<Vec<String>>();
     format!(
-        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
-        log
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"drift\": [{}]}}",
+        drift.join(", ")
+    )
+}
+
+/// Returns the console output in JSON format, lines are keyed by their number as before stderr was captured.
+///
+/// The numbers of the lines that were read from stderr are listed separately under `stderr`.
+pub fn get_console(chat: ConsoleBuffer) -> String {
+    let chat = chat.lock().unwrap(); // FIXME try_lock?
+    let lines = chat
+        .iter()
+        .map(|(num, line, _)| format!("\"{}\":\"{}\"", num, json_escape(line.trim_end_matches(['\r', '\n']))))
+        .collect::<Vec<String>>();
+    let stderr = chat
+        .iter()
+        .filter(|(_, _, stream)| *stream == OutputStream::Stderr)
+        .map(|(num, _, _)| num.to_string())
+        .collect::<Vec<String>>();
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"chat\": {{\n{}\n}}, \"stderr\": [{}]}}",
+        lines.join(",\n"),
+        stderr.join(", ")
     )
 }
 
+/// The command in a `/api/send` query, with underscores standing for spaces as existing clients send them.
 fn query_string(query: &str) -> String {
-    query[1..].replace("_", " ").to_string()
+    query[1..].replace("_", " ")
+}
+
+/// Finds the value of a parameter in a query string of the form `key=value&key=value`.
+fn query_param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query.split('&').find_map(|param| match param.find('=') {
+        Some(loc) if &param[..loc] == key => Some(&param[loc + 1..]),
+        _ => None,
+    })
 }
 
-pub fn send_command(command: &str, web_sender: Sender<String>) -> String {
-    match web_sender.send(query_string(command)) {
+pub fn send_command(command: &str, web_sender: Sender<ConsoleCommand>) -> String {
+    match web_sender.send(ConsoleCommand::new(&query_string(command))) {
         Ok(_) => {
             "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
         }
@@ -81,58 +147,1381 @@ pub fn send_command(command: &str, web_sender: Sender<String>) -> String {
     }
 }
 
-// TODO extract the beginning to its own function and simplify shutdown and restart
+/// Sends a command to the server and waits up to `wait` milliseconds for the lines that it prints in response.
+pub fn send_command_wait(
+    command: &str,
+    wait: &str,
+    web_sender: Sender<ConsoleCommand>,
+    server_output: ConsoleBuffer,
+) -> String {
+    let wait = match wait.parse::<u64>() {
+        Ok(ms) => Duration::from_millis(ms.min(MAX_COMMAND_WAIT)),
+        Err(_) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+    };
+    let command = query_string(command);
+    match command_response(&command, wait, &web_sender, &server_output) {
+        CommandResponse::Unsent => {
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain\r\nConnection: Close"
+                .to_string()
+        }
+        // The command is still queued, it will be sent but there is nothing to report yet
+        CommandResponse::Queued => format!(
+            "HTTP/1.1 202 Accepted\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+            command_response_json(&command, false, &[])
+        ),
+        CommandResponse::Lines(lines) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+            command_response_json(&command, true, &lines)
+        ),
+    }
+}
+
+/// The longest that a command sent through RCON waits to connect and for each part of the response.
+const RCON_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends a command through the server's RCON instead of its console and returns exactly what the server answered.
+pub fn send_command_rcon(command: &str) -> String {
+    let command = query_string(command);
+    let (port, password) = match rcon_settings() {
+        Some(settings) => settings,
+        None => {
+            return format!(
+                "HTTP/1.1 409 Conflict\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+                "RCON is not enabled with a password in server.properties"
+            )
+        }
+    };
+    match RconClient::connect("127.0.0.1", port, &password, RCON_TIMEOUT).and_then(|mut client| client.command(&command)) {
+        Ok(response) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+            command_response_json(&command, true, &response.lines().map(|line| line.to_string()).collect::<Vec<String>>())
+        ),
+        Err(error) => format!(
+            "HTTP/1.1 502 Bad Gateway\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+            error
+        ),
+    }
+}
+
+/// The outcome of waiting for the response to a command.
+pub enum CommandResponse {
+    /// The input thread is not running so the command could not be queued.
+    Unsent,
+    /// The command was not written to the server before the wait ran out.
+    Queued,
+    Lines(Vec<String>),
+}
+
+/// Sends a command to the server and collects the lines that it prints in response.
+///
+/// Lines are collected from the point where the input thread wrote the command to the server.
+/// Once a line matching one of the known responses for the command is found the function waits briefly for any
+/// following lines and then returns, commands without known responses return everything printed during the wait.
+pub fn command_response(
+    command: &str,
+    wait: Duration,
+    web_sender: &Sender<ConsoleCommand>,
+    server_output: &ConsoleBuffer,
+) -> CommandResponse {
+    let (cmd, written) = ConsoleCommand::with_receipt(command);
+    if web_sender.send(cmd).is_err() {
+        return CommandResponse::Unsent;
+    }
+    let start = Instant::now();
+    let first_line = match written.recv_timeout(wait) {
+        Ok(line) => line,
+        Err(_) => return CommandResponse::Queued,
+    };
+    let known_responses = !command_response_patterns(command).is_empty();
+    let mut matched_at = None;
+    let mut lines;
+    loop {
+        {
+            let output = server_output.lock().unwrap(); // FIXME try_lock?
+            lines = output
+                .iter()
+                .rev()
+                // Errors from the JVM are not part of the server's response to a command
+                .filter(|(num, _, stream)| *num >= first_line && *stream == OutputStream::Stdout)
+                .map(|(_, line, _)| line.replace("\n", ""))
+                .filter(|line| !known_responses || is_command_response(command, line_message(line)))
+                .collect::<Vec<String>>();
+        }
+        if known_responses && !lines.is_empty() && matched_at.is_none() {
+            matched_at = Some(Instant::now());
+        }
+        // Give multi-line responses a moment to finish once they have started
+        let settled = matched_at.is_some_and(|at| at.elapsed() >= Duration::from_millis(150));
+        if settled || start.elapsed() >= wait {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    CommandResponse::Lines(lines)
+}
+
+fn command_response_json(command: &str, written: bool, lines: &[String]) -> String {
+    let mut json = format!(
+        "{{\"command\": \"{}\", \"written\": {}, \"lines\": [",
+        json_escape(command),
+        written
+    );
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            json.push_str(", ");
+        }
+        json.push_str(format!("\"{}\"", json_escape(line)).as_str());
+    }
+    json.push_str("]}");
+    json
+}
+
+/// Starts shutting down the Minecraft server and the monitor in the background.
+///
+/// The query can contain a `delay` in seconds and a `message`, players are warned in game while the delay counts down.
+/// The response contains the id of the job that can be followed through `/api/jobs/<id>`.
 pub fn shutdown(
-    mc_state: Arc<Mutex<MinecraftServerState>>,
-    gen_state: Arc<Mutex<GeneralState>>,
-    web_sender: Sender<String>,
+    query: &str,
+    data: shared_data::ServerSharedData,
+    web_sender: Sender<ConsoleCommand>,
+    config: &Configuration,
 ) -> String {
-    // First send command to shutdown minecraft server if it is running, wait until state becomes Off
-    let reference_mc_state = mc_state.lock().unwrap().clone();
-    if reference_mc_state == MinecraftServerState::Running {
-        println!("Sending shutdown");
-        send_command("?stop", web_sender);
-        loop {
-            println!("Start sleep");
-            thread::sleep(Duration::from_millis(500));
-            let current_mc_state = mc_state.lock().unwrap();
-            if *current_mc_state == MinecraftServerState::Off {
-                break;
-            } // Wait until the server has shutdown
-            println!("Release control.");
-        }
-    }
-    // Minecraft server should now be shutdown
-    // change gen state to shutdown
-    let mut ref_gen_state = gen_state.lock().unwrap();
-    *ref_gen_state = GeneralState::ShutDown;
-    return "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nConnection: Close".to_string();
+    start_job(JobAction::Shutdown, query, data, web_sender, config)
 }
 
+/// Starts restarting the Minecraft server in the background.
+///
+/// The query can contain a `delay` in seconds and a `message`, players are warned in game while the delay counts down.
+/// The response contains the id of the job that can be followed through `/api/jobs/<id>`.
 pub fn restart(
-    mc_state: Arc<Mutex<MinecraftServerState>>,
-    gen_state: Arc<Mutex<GeneralState>>,
-    web_sender: Sender<String>,
+    query: &str,
+    data: shared_data::ServerSharedData,
+    web_sender: Sender<ConsoleCommand>,
+    config: &Configuration,
+) -> String {
+    start_job(JobAction::Restart, query, data, web_sender, config)
+}
+
+fn start_job(
+    action: JobAction,
+    query: &str,
+    data: shared_data::ServerSharedData,
+    web_sender: Sender<ConsoleCommand>,
+    config: &Configuration,
 ) -> String {
-    println!("Restarting?");
-    let reference_mc_state = mc_state.lock().unwrap().clone();
-    if reference_mc_state == MinecraftServerState::Running {
-        println!("Running, trying to close");
-        send_command("?stop", web_sender);
-        loop {
-            thread::sleep(Duration::from_millis(500)); // Check every half second if the mc server has shutdown
-            let current_mc_state = mc_state.lock().unwrap();
-            if *current_mc_state == MinecraftServerState::Off {
-                break;
-            } // Wait until the server has shutdown
-        }
-    }
-    // Minecraft server should now be shutdown
-    // change gen state to shutdown
-    let mut ref_gen_state = gen_state.lock().unwrap();
-    *ref_gen_state = GeneralState::Restart;
-    println!("Finish restart");
-    return "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nConnection: Close".to_string();
+    let delay = match query_param(query, "delay").map(|delay| delay.parse::<u64>()) {
+        Some(Ok(delay)) => delay,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => 0,
+    };
+    // A line break in the message would start another command
+    let message = query_param(query, "message").map(|message| message.replace("_", " ").replace(['\r', '\n'], " "));
+    match begin_job(action, delay, message, data, web_sender, config) {
+        Ok(id) => format!(
+            "HTTP/1.1 202 Accepted\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"job\": {}, \"status\": \"/api/jobs/{}\"}}",
+            id, id
+        ),
+        Err(id) => format!(
+            "HTTP/1.1 409 Conflict\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"job\": {}}}",
+            id
+        ),
+    }
+}
+
+/// Starts a shutdown or restart job in the background and returns its id.
+///
+/// Only one job can be happening at a time, if there is already one the error contains its id.
+pub fn begin_job(
+    action: JobAction,
+    delay: u64,
+    message: Option<String>,
+    data: shared_data::ServerSharedData,
+    web_sender: Sender<ConsoleCommand>,
+    config: &Configuration,
+) -> Result<u32, u32> {
+    let id;
+    {
+        let mut jobs = data.jobs.lock().unwrap();
+        if let Some(job) = jobs.iter().find(|job| !job.finished()) {
+            return Err(job.id);
+        }
+        id = jobs.back().map_or(1, |job| job.id + 1);
+        jobs.push_back(Job::new(id, action, delay, message));
+        while jobs.len() > JOB_HISTORY {
+            jobs.pop_front();
+        }
+    }
+    let config = config.clone();
+    thread::spawn(move || run_job(id, action, data, web_sender, config));
+    Ok(id)
+}
+
+fn run_job(
+    id: u32,
+    action: JobAction,
+    data: shared_data::ServerSharedData,
+    web_sender: Sender<ConsoleCommand>,
+    config: Configuration,
+) {
+    let stop_timeout = config.stop_timeout;
+    let (delay, message) = {
+        let jobs = data.jobs.lock().unwrap();
+        match jobs.iter().find(|job| job.id == id) {
+            Some(job) => (job.delay, job.message.clone()),
+            None => return,
+        }
+    };
+    if delay > 0 {
+        if !countdown(id, action, delay, message, &data, &web_sender, &config.countdown_warnings) {
+            return;
+        }
+        save_world(id, &data, &web_sender, Duration::from_secs(stop_timeout));
+    }
+    *data.gen_state.lock().unwrap() = GeneralState::Stopping;
+    let stop_command = config.flavor.stop_command();
+    stop_server(id, &data, web_sender, stop_command, Duration::from_secs(stop_timeout));
+    match action {
+        JobAction::Shutdown => {
+            set_job_stage(&data.jobs, id, JobStage::Stopped);
+            *data.gen_state.lock().unwrap() = GeneralState::ShutDown;
+        }
+        JobAction::Restart => {
+            set_job_stage(&data.jobs, id, JobStage::Restarting);
+            *data.gen_state.lock().unwrap() = GeneralState::Restart;
+            // Follow the next launch until it has either started or failed to
+            let start = Instant::now();
+            let mut relaunched = false;
+            while start.elapsed() < RESTART_TIMEOUT {
+                thread::sleep(Duration::from_millis(500));
+                let mc_state = *data.mcserver_state.lock().unwrap();
+                match mc_state {
+                    MinecraftServerState::Starting => relaunched = true,
+                    MinecraftServerState::Running if relaunched => {
+                        set_job_stage(&data.jobs, id, JobStage::Restarted);
+                        return;
+                    }
+                    MinecraftServerState::Eula if relaunched => break,
+                    _ => {}
+                }
+            }
+            set_job_stage(&data.jobs, id, JobStage::Failed);
+        }
+    }
+}
+
+/// Warns players in game at each of the configured points until the delay has passed.
+///
+/// Returns false if the job was cancelled before the countdown finished.
+fn countdown(
+    id: u32,
+    action: JobAction,
+    delay: u64,
+    message: Option<String>,
+    data: &shared_data::ServerSharedData,
+    web_sender: &Sender<ConsoleCommand>,
+    warnings: &[u64],
+) -> bool {
+    let action = match action {
+        JobAction::Shutdown => "shutting down",
+        JobAction::Restart => "restarting",
+    };
+    set_job_stage(&data.jobs, id, JobStage::Countdown);
+    let start = Instant::now();
+    let delay = Duration::from_secs(delay);
+    // Players are always told about the full delay, the warnings then continue from the next point below it
+    let mut upcoming = warnings
+        .iter()
+        .filter(|seconds| Duration::from_secs(**seconds) < delay)
+        .peekable();
+    let mut announce = Some(delay.as_secs());
+    loop {
+        let cancelled = data
+            .jobs
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|job| job.id == id && job.cancelled);
+        if cancelled {
+            let _ = web_sender.send(ConsoleCommand::new(&format!("say The server is no longer {}", action)));
+            set_job_stage(&data.jobs, id, JobStage::Cancelled);
+            return false;
+        }
+        if let Some(seconds) = announce.take() {
+            let time = countdown_time(seconds);
+            let say = match &message {
+                Some(message) => format!("say The server is {} in {}: {}", action, time, message),
+                None => format!("say The server is {} in {}", action, time),
+            };
+            let _ = web_sender.send(ConsoleCommand::new(&say));
+            let _ = web_sender.send(ConsoleCommand::new(&format!(
+                "title @a actionbar {{\"text\":\"The server is {} in {}\"}}",
+                action, time
+            )));
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= delay {
+            return true;
+        }
+        if let Some(seconds) = upcoming.peek() {
+            if delay - elapsed <= Duration::from_secs(**seconds) {
+                announce = Some(**seconds);
+                upcoming.next();
+                continue;
+            }
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+}
+
+fn countdown_time(seconds: u64) -> String {
+    if seconds >= 60 && seconds.is_multiple_of(60) {
+        match seconds / 60 {
+            1 => "1 minute".to_string(),
+            minutes => format!("{} minutes", minutes),
+        }
+    } else {
+        match seconds {
+            1 => "1 second".to_string(),
+            seconds => format!("{} seconds", seconds),
+        }
+    }
+}
+
+fn save_world(
+    id: u32,
+    data: &shared_data::ServerSharedData,
+    web_sender: &Sender<ConsoleCommand>,
+    timeout: Duration,
+) {
+    if server_process_running(data) {
+        set_job_stage(&data.jobs, id, JobStage::Saving);
+        save_all(data, web_sender, timeout);
+    }
+}
+
+/// Saves the world and waits for the server to report that it has finished saving.
+///
+/// Returns false if the server did not finish saving within the timeout.
+pub fn save_all(
+    data: &shared_data::ServerSharedData,
+    web_sender: &Sender<ConsoleCommand>,
+    timeout: Duration,
+) -> bool {
+    let from_line = data.next_line_number();
+    if web_sender.send(ConsoleCommand::new("save-all")).is_err() {
+        return false;
+    }
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        let saved = data
+            .server_output
+            .lock()
+            .unwrap()
+            .iter()
+            .take_while(|(num, _, _)| *num >= from_line)
+            .any(|(_, line, _)| line_message(line).starts_with("Saved the game"));
+        if saved {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+    false
+}
+
+/// Cancels a shutdown or restart job that is still counting down.
+pub fn cancel_job(id: &str, jobs: Arc<Mutex<VecDeque<Job>>>) -> String {
+    let id = match id.parse::<u32>() {
+        Ok(id) => id,
+        Err(_) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+    };
+    let mut jobs = jobs.lock().unwrap();
+    match jobs.iter_mut().find(|job| job.id == id) {
+        Some(job) => {
+            let counting = job.stage() == JobStage::Queued || job.stage() == JobStage::Countdown;
+            if job.delay > 0 && counting {
+                job.cancelled = true;
+                "HTTP/1.1 202 Accepted\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+            } else {
+                // The server is already being stopped
+                "HTTP/1.1 409 Conflict\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+            }
+        }
+        None => "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: Close".to_string(),
+    }
+}
+
+/// Stops the Minecraft server, if it does not stop within the timeout the process is terminated and then killed.
+fn stop_server(
+    id: u32,
+    data: &shared_data::ServerSharedData,
+    web_sender: Sender<ConsoleCommand>,
+    stop_command: &str,
+    timeout: Duration,
+) {
+    if server_process_running(data) {
+        if web_sender.send(ConsoleCommand::new(stop_command)).is_ok() {
+            set_job_stage(&data.jobs, id, JobStage::StopSent);
+        }
+        set_job_stage(&data.jobs, id, JobStage::Waiting);
+        if !wait_for_exit(data, timeout) {
+            println!("The Minecraft server did not stop in time, terminating the process");
+            set_job_stage(&data.jobs, id, JobStage::Terminated);
+            terminate_child(data);
+            if !wait_for_exit(data, timeout) {
+                println!("The Minecraft server did not terminate in time, killing the process");
+                set_job_stage(&data.jobs, id, JobStage::Killed);
+                if let Some(child) = data.child.lock().unwrap().as_mut() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+            }
+        }
+    }
+    // The input and output threads finish once the server is off
+    *data.mcserver_state.lock().unwrap() = MinecraftServerState::Off;
+}
+
+fn server_process_running(data: &shared_data::ServerSharedData) -> bool {
+    match data.child.lock().unwrap().as_mut() {
+        Some(child) => matches!(child.try_wait(), Ok(None)),
+        None => false,
+    }
+}
+
+fn wait_for_exit(data: &shared_data::ServerSharedData, timeout: Duration) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if !server_process_running(data) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+    !server_process_running(data)
+}
+
+/// Asks the process to exit with SIGTERM, Windows does not have signals so taskkill is used instead.
+fn terminate_child(data: &shared_data::ServerSharedData) {
+    // The child is only reaped through this lock, so holding it keeps the pid from being reused by another process
+    let mut child = data.child.lock().unwrap();
+    let pid = match child.as_mut() {
+        Some(child) => match child.try_wait() {
+            Ok(None) => child.id().to_string(),
+            _ => return,
+        },
+        None => return,
+    };
+    let result = if cfg!(windows) {
+        Command::new("taskkill").args(["/PID", pid.as_str()]).status()
+    } else {
+        Command::new("kill").args(["-s", "TERM", pid.as_str()]).status()
+    };
+    if result.is_err() {
+        println!("Failed to terminate the Minecraft server process: {}", pid);
+    }
+}
+
+fn set_job_stage(jobs: &Arc<Mutex<VecDeque<Job>>>, id: u32, stage: JobStage) {
+    let mut jobs = jobs.lock().unwrap();
+    if let Some(job) = jobs.iter_mut().find(|job| job.id == id) {
+        let elapsed = job.started.elapsed().as_secs_f64();
+        job.stages.push((stage, elapsed));
+    }
+}
+
+/// Returns a String, in JSON format, of the progress of a shutdown or restart job.
+pub fn get_job(id: &str, jobs: Arc<Mutex<VecDeque<Job>>>) -> String {
+    let id = match id.parse::<u32>() {
+        Ok(id) => id,
+        Err(_) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+    };
+    let jobs = jobs.lock().unwrap();
+    let job = match jobs.iter().find(|job| job.id == id) {
+        Some(job) => job,
+        None => {
+            return "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+    };
+    let mut data = format!(
+        "{{\"id\": {}, \"action\": \"{}\", \"stage\": \"{}\", \"finished\": {}, \"elapsed\": {:.1}, \"delay\": {}, \"message\": {}, \"stages\": [",
+        job.id,
+        match job.action {
+            JobAction::Shutdown => "shutdown",
+            JobAction::Restart => "restart",
+        },
+        job.stage().name(),
+        job.finished(),
+        job.started.elapsed().as_secs_f64(),
+        job.delay,
+        match &job.message {
+            Some(message) => format!("\"{}\"", json_escape(message)),
+            None => "null".to_string(),
+        }
+    );
+    for (index, (stage, at)) in job.stages.iter().enumerate() {
+        if index > 0 {
+            data.push_str(", ");
+        }
+        data.push_str(format!("{{\"stage\": \"{}\", \"at\": {:.1}}}", stage.name(), at).as_str());
+    }
+    data.push_str("]}");
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+        data
+    )
+}
+
+/// The longest that the scheduler and input threads can go without going around their loops before they are unhealthy.
+const HEARTBEAT_LIMIT: Duration = Duration::from_secs(10);
+
+/// The longest that the output thread can go without reading a line after a command is written before it is stuck.
+const OUTPUT_LIMIT: Duration = Duration::from_secs(30);
+
+/// The longest that `/readyz` waits for the server to answer its status ping.
+const READY_WAIT: Duration = Duration::from_secs(2);
+
+/// Returns the status and body for a probe, a list of failures in JSON format along with the other given fields.
+fn probe_response(passed: bool, fields: &str, failures: &[String]) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nConnection: Close\r\n\r\n{{{}, \"failures\": [{}]}}",
+        if passed { "200 OK" } else { "503 Service Unavailable" },
+        fields,
+        failures
+            .iter()
+            .map(|failure| format!("\"{}\"", json_escape(failure)))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+/// Checks that the monitor's threads are running and that the output of the server is still being read.
+pub fn get_health(data: &shared_data::ServerSharedData) -> String {
+    let state = *data.mcserver_state.lock().unwrap();
+    let heartbeats = data.heartbeats.lock().unwrap().clone();
+    let mut failures = Vec::new();
+    for (thread, heartbeat) in [("scheduler", heartbeats.scheduler), ("input", heartbeats.input)] {
+        if let Some(elapsed) = heartbeat.map(|heartbeat| heartbeat.elapsed()).filter(|elapsed| *elapsed > HEARTBEAT_LIMIT) {
+            failures.push(format!("The {} thread has not run for {} seconds", thread, elapsed.as_secs()));
+        }
+    }
+    // A quiet server prints nothing, the reader is only stuck when a command has gone unanswered
+    if state == MinecraftServerState::Starting || state == MinecraftServerState::Running {
+        if let Some(written) = heartbeats.command_written {
+            let answered = heartbeats.output_read.is_some_and(|read| read >= written);
+            if !answered && written.elapsed() > OUTPUT_LIMIT {
+                failures.push(format!(
+                    "No output has been read in the {} seconds since a command was written",
+                    written.elapsed().as_secs()
+                ));
+            }
+        }
+    }
+    probe_response(
+        failures.is_empty(),
+        &format!("\"healthy\": {}, \"state\": \"{}\"", failures.is_empty(), state.name()),
+        &failures,
+    )
+}
+
+/// Checks that the server is running and answers a Server List Ping on its game port, as a player's client would.
+pub fn get_readiness(data: &shared_data::ServerSharedData) -> String {
+    let state = *data.mcserver_state.lock().unwrap();
+    let mut failures = Vec::new();
+    let ping = if state != MinecraftServerState::Running {
+        failures.push(format!("The server is {}", state.name()));
+        "null"
+    } else {
+        let address = server_property("server-ip").filter(|ip| !ip.is_empty());
+        match server_list_ping(address.as_deref().unwrap_or("127.0.0.1"), game_port(), READY_WAIT) {
+            Ok(_) => "\"ok\"",
+            Err(error) => {
+                failures.push(format!("The server did not answer a status ping: {}", error));
+                "\"failed\""
+            }
+        }
+    };
+    probe_response(
+        failures.is_empty(),
+        &format!("\"ready\": {}, \"state\": \"{}\", \"ping\": {}", failures.is_empty(), state.name(), ping),
+        &failures,
+    )
+}
+
+/// Adds a metric to a Prometheus text exposition, each sample is its labels, such as `{stream="stdout"}`, and its value.
+fn metric(exposition: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, String)]) {
+    exposition.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+    for (labels, value) in samples {
+        exposition.push_str(&format!("{}{} {}\n", name, labels, value));
+    }
+}
+
+/// Returns the state of the monitor and the server in the Prometheus text exposition format.
+pub fn get_metrics(data: &shared_data::ServerSharedData) -> String {
+    let state = *data.mcserver_state.lock().unwrap();
+    let counters = data.counters.lock().unwrap().clone();
+    let unlabelled = |value: String| vec![(String::new(), value)];
+    let mut exposition = String::new();
+    metric(
+        &mut exposition,
+        "minecraft_players_online",
+        "gauge",
+        "Players online.",
+        &unlabelled(data.current_player_count.lock().unwrap().to_string()),
+    );
+    metric(
+        &mut exposition,
+        "minecraft_players_max",
+        "gauge",
+        "The most players that can be online.",
+        &unlabelled(data.max_player_count.lock().unwrap().to_string()),
+    );
+    let states = [
+        MinecraftServerState::Off,
+        MinecraftServerState::Starting,
+        MinecraftServerState::Running,
+        MinecraftServerState::Eula,
+    ]
+    .iter()
+    .map(|each| {
+        (
+            format!("{{state=\"{}\"}}", each.name()),
+            ((*each == state) as u8).to_string(),
+        )
+    })
+    .collect::<Vec<(String, String)>>();
+    metric(
+        &mut exposition,
+        "minecraft_server_state",
+        "gauge",
+        "The state of the Minecraft server, 1 for the current state.",
+        &states,
+    );
+    metric(
+        &mut exposition,
+        "minecraft_monitor_uptime_seconds",
+        "gauge",
+        "Seconds since the monitor started.",
+        &unlabelled(format!("{:.3}", counters.started.elapsed().as_secs_f64())),
+    );
+    metric(
+        &mut exposition,
+        "minecraft_server_uptime_seconds",
+        "gauge",
+        "Seconds since the Minecraft server process was launched, 0 when there is none.",
+        &unlabelled(format!(
+            "{:.3}",
+            counters
+                .launched
+                .map_or(0.0, |launched| launched.elapsed().as_secs_f64())
+        )),
+    );
+    metric(
+        &mut exposition,
+        "minecraft_server_restarts_total",
+        "counter",
+        "Times that the Minecraft server has been launched again.",
+        &unlabelled(counters.launches.saturating_sub(1).to_string()),
+    );
+    metric(
+        &mut exposition,
+        "minecraft_server_crashes_total",
+        "counter",
+        "Times that the Minecraft server has crashed.",
+        &unlabelled(data.crashes.lock().unwrap().len().to_string()),
+    );
+    let lines = vec![
+        ("{stream=\"stdout\"}".to_string(), counters.stdout_lines.to_string()),
+        ("{stream=\"stderr\"}".to_string(), counters.stderr_lines.to_string()),
+    ];
+    metric(
+        &mut exposition,
+        "minecraft_console_lines_total",
+        "counter",
+        "Lines of console output that have been read.",
+        &lines,
+    );
+    metric(
+        &mut exposition,
+        "minecraft_commands_sent_total",
+        "counter",
+        "Commands written to the Minecraft server.",
+        &unlabelled(counters.commands_sent.to_string()),
+    );
+    let responses = counters
+        .responses
+        .iter()
+        .map(|(status, count)| (format!("{{status=\"{}\"}}", status), count.to_string()))
+        .collect::<Vec<(String, String)>>();
+    metric(
+        &mut exposition,
+        "minecraft_http_requests_total",
+        "counter",
+        "Web requests by the status of their response.",
+        &responses,
+    );
+    let process = data
+        .system
+        .lock()
+        .unwrap()
+        .back()
+        .and_then(|sample| sample.process.clone());
+    if let Some(process) = process.filter(|_| counters.launched.is_some()) {
+        metric(
+            &mut exposition,
+            "minecraft_process_resident_memory_bytes",
+            "gauge",
+            "Resident memory of the Minecraft server process.",
+            &unlabelled(process.rss.to_string()),
+        );
+        if let Some(cpu) = process.cpu {
+            metric(
+                &mut exposition,
+                "minecraft_process_cpu_percent",
+                "gauge",
+                "Percent of a core used by the Minecraft server process.",
+                &unlabelled(format!("{:.2}", cpu)),
+            );
+        }
+    }
+    // Only reported while running, a measurement from before a restart says nothing about the server now
+    let performance = data.performance.lock().unwrap().back().cloned();
+    if let Some(sample) = performance.filter(|_| state == MinecraftServerState::Running) {
+        if let Some(tps) = sample.tps {
+            metric(
+                &mut exposition,
+                "minecraft_tps",
+                "gauge",
+                "Ticks per second at the last measurement.",
+                &unlabelled(format!("{:.2}", tps)),
+            );
+        }
+        if let Some(mspt) = sample.mspt {
+            metric(
+                &mut exposition,
+                "minecraft_mspt",
+                "gauge",
+                "Milliseconds per tick at the last measurement.",
+                &unlabelled(format!("{:.2}", mspt)),
+            );
+        }
+    }
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nConnection: Close\r\n\r\n{}",
+        exposition
+    )
+}
+
+/// Returns a String, in JSON format, of how far the server has got through starting and how long previous starts took.
+pub fn get_startup(data: &shared_data::ServerSharedData) -> String {
+    let state = *data.mcserver_state.lock().unwrap();
+    let startup = data.startup.lock().unwrap().clone();
+    let history = data.startup_history.lock().unwrap();
+    let starts = history
+        .iter()
+        .map(|record| {
+            format!(
+                "{{\"time\": {}, \"duration\": {:.1}, \"reported\": {}}}",
+                json_time(Some(record.time)),
+                record.duration,
+                record.reported.map_or("null".to_string(), |reported| format!("{:.1}", reported))
+            )
+        })
+        .collect::<Vec<String>>();
+    let average = match history.len() {
+        0 => "null".to_string(),
+        starts => format!("{:.1}", history.iter().map(|record| record.duration).sum::<f64>() / starts as f64),
+    };
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"state\": \"{}\", \"stage\": \"{}\", \"percent\": {}, \"stage_percent\": {}, \"elapsed\": {:.1}, \"duration\": {}, \"average\": {}, \"history\": [{}]}}",
+        state.name(),
+        startup.stage.name(),
+        startup.percent(),
+        startup.stage_percent.map_or("null".to_string(), |percent| percent.to_string()),
+        startup.duration.unwrap_or_else(|| startup.started.elapsed().as_secs_f64()),
+        startup.duration.map_or("null".to_string(), |duration| format!("{:.1}", duration)),
+        average,
+        starts.join(", ")
+    )
+}
+
+/// Returns a String, in JSON format, of everything known about a player and their recent sessions.
+pub fn get_player(name: &str, players: Arc<Mutex<Vec<PlayerRecord>>>) -> String {
+    let players = players.lock().unwrap();
+    let player = match players.iter().find(|player| player.name.eq_ignore_ascii_case(name)) {
+        Some(player) => player,
+        None => return "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: Close".to_string(),
+    };
+    let now = unix_time();
+    let optional = |value: &Option<String>| match value {
+        Some(value) => format!("\"{}\"", json_escape(value)),
+        None => "null".to_string(),
+    };
+    let sessions = player
+        .sessions
+        .iter()
+        .map(|session| {
+            format!(
+                "{{\"joined\": {}, \"left\": {}, \"duration\": {}, \"reason\": {}, \"ip\": {}}}",
+                json_time(Some(session.joined)),
+                json_time(session.left),
+                session.left.unwrap_or(now).saturating_sub(session.joined),
+                optional(&session.reason),
+                optional(&session.ip)
+            )
+        })
+        .collect::<Vec<String>>();
+    let advancements = player
+        .advancements
+        .iter()
+        .map(|title| format!("\"{}\"", json_escape(title)))
+        .collect::<Vec<String>>();
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"name\": \"{}\", \"uuid\": {}, \"ip\": {}, \"online\": {}, \"playtime\": {}, \"last_seen\": {}, \"deaths\": {}, \"advancements\": [{}], \"sessions\": [{}]}}",
+        json_escape(&player.name),
+        optional(&player.uuid),
+        optional(&player.ip),
+        player.online(),
+        player.total_playtime(now),
+        json_time(player.last_seen(now)),
+        player.deaths,
+        advancements.join(", "),
+        sessions.join(", ")
+    )
+}
+
+/// Returns a String, in JSON format, of the events after the id given as `since`, oldest first.
+///
+/// The query can also contain a `type` of `join`, `leave`, `death` or `advancement` and a `player`. Polling with the
+/// `last` id from the previous response follows the events as they happen.
+///
+/// Ids start again from 1 when the monitor restarts, a `since` past the newest event is answered with a conflict and
+/// the events should be requested again without it.
+pub fn get_events(query: &str, events: Arc<Mutex<VecDeque<GameEvent>>>) -> String {
+    let since = match query_param(query, "since").map(|since| since.parse::<u64>()) {
+        Some(Ok(since)) => since,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => 0,
+    };
+    let kind = query_param(query, "type");
+    let player = query_param(query, "player");
+    let events = events.lock().unwrap();
+    let last = events.back().map_or(0, |event| event.id);
+    if since > last {
+        return format!(
+            "HTTP/1.1 409 Conflict\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"last\": {}}}",
+            last
+        );
+    }
+    let optional = |value: &Option<String>| match value {
+        Some(value) => format!("\"{}\"", json_escape(value)),
+        None => "null".to_string(),
+    };
+    let matching = events
+        .iter()
+        .filter(|event| event.id > since)
+        .filter(|event| kind.is_none_or(|kind| event.kind.name() == kind))
+        .filter(|event| player.is_none_or(|player| event.player.eq_ignore_ascii_case(player)))
+        .map(|event| {
+            let details = match &event.kind {
+                GameEventKind::Join | GameEventKind::Leave => String::new(),
+                GameEventKind::Death { cause, killer, message } => format!(
+                    ", \"cause\": \"{}\", \"killer\": {}, \"message\": \"{}\"",
+                    cause,
+                    optional(killer),
+                    json_escape(message)
+                ),
+                GameEventKind::Advancement { kind, title } => format!(
+                    ", \"kind\": \"{}\", \"title\": \"{}\"",
+                    kind,
+                    json_escape(title)
+                ),
+            };
+            format!(
+                "{{\"id\": {}, \"time\": {}, \"type\": \"{}\", \"player\": \"{}\"{}}}",
+                event.id,
+                json_time(Some(event.time)),
+                event.kind.name(),
+                json_escape(&event.player),
+                details
+            )
+        })
+        .collect::<Vec<String>>();
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"last\": {}, \"events\": [{}]}}",
+        last,
+        matching.join(", ")
+    )
+}
+
+/// Returns a String, in JSON format, of the warnings and errors that the server has printed, the most recent first.
+/// The query can contain a `level` to only return alerts at least that severe, and a `limit`.
+pub fn get_alerts(query: &str, alerts: Arc<Mutex<Vec<Alert>>>, lag: Arc<Mutex<LagStats>>) -> String {
+    let level = match query_param(query, "level").map(|level| LogLevel::parse(&level.to_uppercase())) {
+        Some(None) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        Some(level) => level,
+        None => None,
+    };
+    let limit = match query_param(query, "limit").map(|limit| limit.parse::<usize>()) {
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => usize::MAX,
+    };
+    let optional = |value: &Option<String>| match value {
+        Some(value) => format!("\"{}\"", json_escape(value)),
+        None => "null".to_string(),
+    };
+    let mut alerts = alerts
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|alert| level.is_none_or(|level| alert.level >= level))
+        .cloned()
+        .collect::<Vec<Alert>>();
+    alerts.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then(b.id.cmp(&a.id)));
+    let alerts = alerts
+        .iter()
+        .take(limit)
+        .map(|alert| {
+            format!(
+                "{{\"id\": {}, \"level\": \"{}\", \"message\": \"{}\", \"thread\": {}, \"logger\": {}, \"count\": {}, \"first_seen\": {}, \"last_seen\": {}, \"trace\": [{}]}}",
+                alert.id,
+                alert.level.name(),
+                json_escape(&alert.message),
+                optional(&alert.thread),
+                optional(&alert.logger),
+                alert.count,
+                json_time(Some(alert.first_seen)),
+                json_time(Some(alert.last_seen)),
+                alert
+                    .trace
+                    .iter()
+                    .map(|line| format!("\"{}\"", json_escape(line)))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+        })
+        .collect::<Vec<String>>();
+    let lag = lag.lock().unwrap();
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"lag\": {{\"count\": {}, \"skipped_ms\": {}, \"skipped_ticks\": {}, \"max_ms\": {}, \"last\": {}}}, \"alerts\": [{}]}}",
+        lag.count,
+        lag.skipped_ms,
+        lag.skipped_ticks,
+        lag.max_ms,
+        json_time(lag.last),
+        alerts.join(", ")
+    )
+}
+
+/// Returns a String, in JSON format, of the measurements of the server's ticks per second and milliseconds per tick.
+/// The query can contain `since`, a Unix time, and a `limit` on the number of the most recent measurements.
+pub fn get_performance(query: &str, performance: Arc<Mutex<VecDeque<PerformanceSample>>>) -> String {
+    let since = match query_param(query, "since").map(|since| since.parse::<u64>()) {
+        Some(Ok(since)) => since,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => 0,
+    };
+    let limit = match query_param(query, "limit").map(|limit| limit.parse::<usize>()) {
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => usize::MAX,
+    };
+    let performance = performance.lock().unwrap();
+    let samples = performance.iter().filter(|sample| sample.time > since).collect::<Vec<&PerformanceSample>>();
+    let samples = &samples[samples.len().saturating_sub(limit)..];
+    let number = |value: Option<f64>| value.map_or("null".to_string(), |value| format!("{:.2}", value));
+    let json = |sample: &PerformanceSample| {
+        format!(
+            "{{\"time\": {}, \"tps\": {}, \"mspt\": {}, \"source\": \"{}\"}}",
+            json_time(Some(sample.time)),
+            number(sample.tps),
+            number(sample.mspt),
+            sample.source.name()
+        )
+    };
+    let average = |values: Vec<f64>| match values.len() {
+        0 => None,
+        count => Some(values.iter().sum::<f64>() / count as f64),
+    };
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"current\": {}, \"average\": {{\"tps\": {}, \"mspt\": {}}}, \"samples\": [{}]}}",
+        performance.back().map_or("null".to_string(), json),
+        number(average(samples.iter().filter_map(|sample| sample.tps).collect())),
+        number(average(samples.iter().filter_map(|sample| sample.mspt).collect())),
+        samples.iter().map(|sample| json(sample)).collect::<Vec<String>>().join(", ")
+    )
+}
+
+/// Returns a String, in JSON format, of the resource use of the server's process and the host, next to the memory
+/// that the server was configured with. The query can contain a `limit` on the number of the most recent samples.
+pub fn get_system(query: &str, system: Arc<Mutex<VecDeque<SystemSample>>>, config: &Configuration) -> String {
+    let limit = match query_param(query, "limit").map(|limit| limit.parse::<usize>()) {
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => usize::MAX,
+    };
+    let number = |value: Option<u64>| value.map_or("null".to_string(), |value| value.to_string());
+    let decimal = |value: Option<f64>| value.map_or("null".to_string(), |value| format!("{:.2}", value));
+    let json = |sample: &SystemSample| {
+        let process = match &sample.process {
+            Some(process) => format!(
+                "{{\"pid\": {}, \"rss\": {}, \"cpu\": {}, \"threads\": {}, \"open_files\": {}, \"read_bytes\": {}, \"write_bytes\": {}}}",
+                process.pid,
+                process.rss,
+                decimal(process.cpu),
+                process.threads,
+                process.open_files,
+                number(process.read_bytes),
+                number(process.write_bytes)
+            ),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\"time\": {}, \"process\": {}, \"load\": {}, \"memory\": {{\"total\": {}, \"available\": {}}}, \"disk\": {{\"total\": {}, \"free\": {}}}}}",
+            json_time(Some(sample.time)),
+            process,
+            sample.load.map_or("null".to_string(), |(one, five, fifteen)| format!("[{:.2}, {:.2}, {:.2}]", one, five, fifteen)),
+            number(sample.memory_total),
+            number(sample.memory_available),
+            number(sample.disk_total),
+            number(sample.disk_free)
+        )
+    };
+    let system = system.lock().unwrap();
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"minimum_ram\": \"{}\", \"maximum_ram\": \"{}\", \"current\": {}, \"history\": [{}]}}",
+        config.min_ram,
+        config.max_ram,
+        system.back().map_or("null".to_string(), json),
+        system
+            .iter()
+            .skip(system.len().saturating_sub(limit))
+            .map(json)
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+/// The longest that `/api/ping` waits for each step of the Server List Ping.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Returns a String, in JSON format, of the status that a server reports through the Server List Ping.
+/// The query can contain an `address` and `port`, which default to this server.
+pub fn get_ping(query: &str) -> String {
+    let address = query_param(query, "address").unwrap_or("127.0.0.1");
+    let port = match query_param(query, "port").map(|port| port.parse::<u16>()) {
+        Some(Ok(port)) => port,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => game_port(),
+    };
+    let status = match server_list_ping(address, port, PING_TIMEOUT) {
+        Ok(status) => status,
+        Err(error) => {
+            return format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"address\": \"{}\", \"port\": {}, \"online\": false, \"error\": \"{}\"}}",
+                json_escape(address),
+                port,
+                json_escape(&error.to_string())
+            )
+        }
+    };
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"address\": \"{}\", \"port\": {}, \"online\": true, \"version\": \"{}\", \"protocol\": {}, \"players\": {{\"online\": {}, \"max\": {}, \"sample\": [{}]}}, \"motd\": \"{}\", \"latency\": {}}}",
+        json_escape(address),
+        port,
+        json_escape(&status.version),
+        status.protocol,
+        status.online,
+        status.max,
+        status.sample.iter().map(|name| format!("\"{}\"", json_escape(name))).collect::<Vec<String>>().join(", "),
+        json_escape(&status.motd),
+        status.latency.map_or("null".to_string(), |latency| format!("{:.1}", latency.as_secs_f64() * 1000.0))
+    )
+}
+
+/// Returns a String, in JSON format, of what a server reports through Query. The query can contain an `address` and
+/// `port`, which default to this server, and `full` for the version, plugins and every player.
+pub fn get_query(query: &str) -> String {
+    let address = query_param(query, "address").unwrap_or("127.0.0.1");
+    let port = match query_param(query, "port").map(|port| port.parse::<u16>()) {
+        Some(Ok(port)) => port,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => query_port(),
+    };
+    let full = query.split('&').any(|param| param == "full" || param.starts_with("full="));
+    let status = match query_server(address, port, full, PING_TIMEOUT) {
+        Ok(status) => status,
+        Err(error) => {
+            return format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"address\": \"{}\", \"port\": {}, \"online\": false, \"error\": \"{}\"}}",
+                json_escape(address),
+                port,
+                json_escape(&error.to_string())
+            )
+        }
+    };
+    let optional = |text: &Option<String>| text.as_ref().map_or("null".to_string(), |text| format!("\"{}\"", json_escape(text)));
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"address\": \"{}\", \"port\": {}, \"online\": true, \"motd\": \"{}\", \"game_type\": \"{}\", \"map\": \"{}\", \"players\": {{\"online\": {}, \"max\": {}, \"list\": [{}]}}, \"host_port\": {}, \"host_ip\": \"{}\", \"version\": {}, \"plugins\": {}}}",
+        json_escape(address),
+        port,
+        json_escape(&status.motd),
+        json_escape(&status.game_type),
+        json_escape(&status.map),
+        status.online,
+        status.max,
+        status.players.iter().map(|name| format!("\"{}\"", json_escape(name))).collect::<Vec<String>>().join(", "),
+        status.host_port,
+        json_escape(&status.host_ip),
+        optional(&status.version),
+        optional(&status.plugins)
+    )
+}
+
+/// The number of players that are returned by the leaderboard when no limit is given.
+const LEADERBOARD_LIMIT: usize = 10;
+
+/// Returns a String, in JSON format, of the players with the most playtime, the query can contain a `limit`.
+pub fn get_leaderboard(query: &str, players: Arc<Mutex<Vec<PlayerRecord>>>) -> String {
+    let limit = match query_param(query, "limit").map(|limit| limit.parse::<usize>()) {
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => LEADERBOARD_LIMIT,
+    };
+    let now = unix_time();
+    let players = players.lock().unwrap();
+    let mut ranked = players
+        .iter()
+        .map(|player| (player.total_playtime(now), player))
+        .collect::<Vec<(u64, &PlayerRecord)>>();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.name.cmp(&b.1.name)));
+    let entries = ranked
+        .iter()
+        .take(limit)
+        .enumerate()
+        .map(|(index, (playtime, player))| {
+            format!(
+                "{{\"rank\": {}, \"name\": \"{}\", \"playtime\": {}, \"online\": {}}}",
+                index + 1,
+                json_escape(&player.name),
+                playtime,
+                player.online()
+            )
+        })
+        .collect::<Vec<String>>();
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"players\": [{}]}}",
+        entries.join(", ")
+    )
+}
+
+/// The number of chat messages that are returned when no limit is given.
+const CHAT_LIMIT: usize = 100;
+
+/// Returns a String, in JSON format, of the chat messages matching the query, oldest first.
+///
+/// The query can contain `player`, `since` as seconds since the Unix epoch, `q` to search the messages with spaces as
+/// underscores and `limit`. Only the most recent matches up to the limit are returned.
+pub fn get_chat(query: &str, chat: Arc<Mutex<VecDeque<ChatMessage>>>) -> String {
+    let player = query_param(query, "player").map(|player| player.to_lowercase());
+    let since = match query_param(query, "since").map(|since| since.parse::<u64>()) {
+        Some(Ok(since)) => since,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => 0,
+    };
+    let search = query_param(query, "q").map(|search| search.replace("_", " ").to_lowercase());
+    let limit = match query_param(query, "limit").map(|limit| limit.parse::<usize>()) {
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => {
+            return "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+        None => CHAT_LIMIT,
+    };
+    let chat = chat.lock().unwrap();
+    let mut messages = chat
+        .iter()
+        .rev()
+        .filter(|chat| chat.time >= since)
+        .filter(|chat| player.as_ref().is_none_or(|player| chat.player.to_lowercase() == *player))
+        .filter(|chat| search.as_ref().is_none_or(|search| chat.message.to_lowercase().contains(search.as_str())))
+        .take(limit)
+        .map(|chat| {
+            format!(
+                "{{\"time\": {}, \"player\": \"{}\", \"kind\": \"{}\", \"message\": \"{}\"}}",
+                json_time(Some(chat.time)),
+                json_escape(&chat.player),
+                chat.kind.name(),
+                json_escape(&chat.message)
+            )
+        })
+        .collect::<Vec<String>>();
+    messages.reverse();
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"messages\": [{}]}}",
+        messages.join(", ")
+    )
+}
+
+/// Returns a String, in JSON format, of the kind of server being run and the commands that it has.
+pub fn get_flavor(flavor: &dyn ServerFlavor) -> String {
+    let capabilities = flavor
+        .capabilities()
+        .iter()
+        .map(|capability| format!("\"{}\"", capability.command()))
+        .collect::<Vec<String>>();
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"flavor\": \"{}\", \"capabilities\": [{}]}}",
+        flavor.name(),
+        capabilities.join(", ")
+    )
+}
+
+/// Returns a String, in JSON format, of the crashes since the monitor started with the output leading up to each.
+pub fn get_crashes(crashes: Arc<Mutex<Vec<CrashReport>>>) -> String {
+    let crashes = crashes.lock().unwrap();
+    let mut data = "{\"crashes\": [".to_string();
+    for (index, crash) in crashes.iter().enumerate() {
+        if index > 0 {
+            data.push_str(", ");
+        }
+        data.push_str(
+            format!(
+                "{{\"time\": {}, \"exit_code\": {}, \"status\": \"{}\", \"lines\": [",
+                json_time(Some(crash.time)),
+                match crash.exit_code {
+                    Some(code) => code.to_string(),
+                    None => "null".to_string(),
+                },
+                json_escape(&crash.status)
+            )
+            .as_str(),
+        );
+        for (index, line) in crash.lines.iter().enumerate() {
+            if index > 0 {
+                data.push_str(", ");
+            }
+            data.push_str(format!("\"{}\"", json_escape(line)).as_str());
+        }
+        data.push_str("]}");
+    }
+    data.push_str("]}");
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+        data
+    )
+}
+
+/// Returns a String, in JSON format, of the scheduled tasks with their next and last run times.
+pub fn get_schedule(schedule: Arc<Mutex<Vec<ScheduledTask>>>) -> String {
+    // Finding the next runs can take a while, the scheduler should not have to wait for it
+    let schedule = schedule.lock().unwrap().clone();
+    let mut data = "{\"tasks\": [".to_string();
+    for (index, task) in schedule.iter().enumerate() {
+        if index > 0 {
+            data.push_str(", ");
+        }
+        data.push_str(
+            format!(
+                "{{\"id\": {}, \"schedule\": \"{}\", \"action\": \"{}\", \"paused\": {}, \"next_run\": {}, \"last_run\": {}, \"last_result\": {}}}",
+                task.id,
+                json_escape(&task.expression),
+                json_escape(&task.action.describe()),
+                task.paused,
+                json_time(task.next_run()),
+                json_time(task.last_run),
+                match &task.last_result {
+                    Some(result) => format!("\"{}\"", json_escape(result)),
+                    None => "null".to_string(),
+                }
+            )
+            .as_str(),
+        );
+    }
+    data.push_str("]}");
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+        data
+    )
+}
+
+fn json_time(time: Option<u64>) -> String {
+    match time {
+        Some(time) => format!("{{\"time\": {}, \"local\": \"{}\"}}", time, format_time(time)),
+        None => "null".to_string(),
+    }
+}
+
+/// Adds a task to the schedule, the query is the same as a schedule in the configuration file, percent-encoded.
+///
+/// Tasks added while running are not saved to the configuration file.
+pub fn add_schedule(query: &str, schedule: Arc<Mutex<Vec<ScheduledTask>>>) -> String {
+    let task = percent_decode(query);
+    let mut schedule = schedule.lock().unwrap();
+    let id = schedule.iter().map(|task| task.id).max().unwrap_or(0) + 1;
+    match ScheduledTask::parse(id, &task) {
+        Ok(task) => {
+            schedule.push(task);
+            format!(
+                "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"id\": {}}}",
+                id
+            )
+        }
+        Err(error) => format!(
+            "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+            error
+        ),
+    }
+}
+
+/// Pauses, resumes or removes a scheduled task, the request is of the form `<id>/<action>`.
+pub fn modify_schedule(request: &str, schedule: Arc<Mutex<Vec<ScheduledTask>>>) -> String {
+    let (id, action) = match request.find('/') {
+        Some(loc) => (request[..loc].parse::<u32>(), &request[loc + 1..]),
+        None => {
+            return "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+    };
+    let mut schedule = schedule.lock().unwrap();
+    let index = match id.map(|id| schedule.iter().position(|task| task.id == id)) {
+        Ok(Some(index)) => index,
+        _ => return "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: Close".to_string(),
+    };
+    match action {
+        "pause" => schedule[index].paused = true,
+        "resume" => schedule[index].paused = false,
+        "remove" => {
+            schedule.remove(index);
+        }
+        _ => return "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: Close".to_string(),
+    }
+    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
 }
 
 pub fn accept_eula(
@@ -147,16 +1536,432 @@ pub fn accept_eula(
         fs::write("eula.txt", new_eula).unwrap();
         let mut ref_gen_state = data.gen_state.lock().unwrap();
         *ref_gen_state = GeneralState::Restart;
-        return "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nConnection: Close".to_string();
+        "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
     } else {
-        return "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nConnection: Close".to_string(); // Change to return an error thing
+        "HTTP/1.1 201 Created\r\nContent-Type: text/plain\r\nConnection: Close".to_string() // Change to return an error thing
+    }
+}
+
+/// Decodes `%XX` escapes, and `+` as a space, in a value from a query string.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => match bytes
+                .get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    index += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// The properties in JSON, each marked with whether the running server is using a different value.
+fn properties_json(data: &shared_data::ServerSharedData, properties: &ServerProperties) -> (bool, String) {
+    let running = *data.mcserver_state.lock().unwrap() == MinecraftServerState::Running;
+    let loaded = data.loaded_properties.lock().unwrap();
+    let mut restart_needed = false;
+    let entries = properties
+        .entries()
+        .map(|(key, value)| {
+            // A server that is not running reads the file again when it starts
+            let restart = running && loaded.as_ref().is_some_and(|loaded| loaded.get(key) != Some(value));
+            restart_needed |= restart;
+            format!(
+                "{{\"key\": \"{}\", \"value\": \"{}\", \"type\": \"{}\", \"restart\": {}}}",
+                json_escape(key),
+                json_escape(value),
+                property_kind(key).name(),
+                restart
+            )
+        })
+        .collect::<Vec<String>>();
+    (restart_needed, entries.join(", "))
+}
+
+/// Returns a String, in JSON format, of every property in `server.properties` in the order of the file.
+pub fn get_properties(data: &shared_data::ServerSharedData) -> String {
+    let properties = match ServerProperties::load() {
+        Ok(properties) => properties,
+        Err(_) => {
+            return "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+    };
+    let (restart_needed, entries) = properties_json(data, &properties);
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"restart_needed\": {}, \"properties\": [{}]}}",
+        restart_needed, entries
+    )
+}
+
+/// Changes properties given as `key=value` in the query, values are percent encoded. Nothing is changed unless every
+/// value is valid, otherwise the problems are returned.
+pub fn set_properties(query: &str, data: &shared_data::ServerSharedData) -> String {
+    let changes = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) => (percent_decode(key), percent_decode(value)),
+            None => (percent_decode(param), String::new()),
+        })
+        .collect::<Vec<(String, String)>>();
+    let errors = changes
+        .iter()
+        .filter_map(|(key, value)| validate_property(key, value).err())
+        .map(|error| format!("\"{}\"", json_escape(&error)))
+        .collect::<Vec<String>>();
+    if changes.is_empty() || !errors.is_empty() {
+        return format!(
+            "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"errors\": [{}]}}",
+            errors.join(", ")
+        );
+    }
+    let mut properties = match ServerProperties::load() {
+        Ok(properties) => properties,
+        Err(_) => {
+            return "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+        }
+    };
+    let changed = changes
+        .iter()
+        .filter(|(key, value)| properties.set(key, value))
+        .map(|(key, _)| format!("\"{}\"", json_escape(key)))
+        .collect::<Vec<String>>();
+    if properties.save().is_err() {
+        return "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain\r\nConnection: Close".to_string();
+    }
+    let (restart_needed, entries) = properties_json(data, &properties);
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"changed\": [{}], \"restart_needed\": {}, \"properties\": [{}]}}",
+        changed.join(", "),
+        restart_needed,
+        entries
+    )
+}
+
+/// The longest that a change to a list waits for the server to answer its command.
+const LIST_COMMAND_WAIT: Duration = Duration::from_secs(5);
+
+/// Returns a String, in JSON format, of the entries of the whitelist, operators or bans as they are in the server's file.
+pub fn get_player_list(name: &str) -> String {
+    let list = match PlayerList::from_name(name) {
+        Some(list) => list,
+        None => return "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: Close".to_string(),
+    };
+    match load_list(list) {
+        Ok(entries) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"list\": \"{}\", \"entries\": {}}}",
+            list.name(),
+            Json::Array(entries)
+        ),
+        Err(error) => format!(
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+            error
+        ),
+    }
+}
+
+/// Adds a player or address to a list, or removes them, as `action` is `add` or `remove`. The query gives the `name`
+/// of the player or the `ip` for IP bans, and optionally a `reason`, `expires` in seconds from now and the `level` of an
+/// operator, all percent encoded.
+///
+/// A running server is sent the list's command, which can not set an expiry or level, otherwise the file is edited. A
+/// starting server has already read the files but can not take commands yet, and a stopping server would write over
+/// them, so nothing is changed until it has exited.
+pub fn modify_player_list(
+    name: &str,
+    action: &str,
+    query: &str,
+    data: shared_data::ServerSharedData,
+    web_sender: Sender<ConsoleCommand>,
+) -> String {
+    let bad_request = |reason: &str| {
+        format!(
+            "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+            reason
+        )
+    };
+    let list = match PlayerList::from_name(name) {
+        Some(list) if action == "add" || action == "remove" => list,
+        _ => return "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: Close".to_string(),
+    };
+    let param = |key: &str| query_param(query, key).map(percent_decode);
+    let target = match param(if list == PlayerList::IpBans { "ip" } else { "name" }) {
+        // Anything after a space would be taken as part of the command
+        Some(target) if !target.is_empty() && !target.contains(char::is_whitespace) => target,
+        _ => return bad_request("A name, or an ip for IP bans, without spaces is needed"),
+    };
+    // A line break in the reason would start another command
+    let reason = param("reason").map(|reason| reason.replace(['\r', '\n'], " "));
+    let expires = match param("expires").map(|seconds| seconds.parse::<u64>()) {
+        Some(Ok(seconds)) => Some(unix_time() + seconds),
+        Some(Err(_)) => return bad_request("The expiry must be a whole number of seconds from now"),
+        None => None,
+    };
+    let level = match param("level").map(|level| level.parse::<u32>()) {
+        Some(Ok(level)) if level <= 4 => Some(level),
+        Some(_) => return bad_request("The level of an operator must be from 0 to 4"),
+        None => None,
+    };
+
+    let state = *data.mcserver_state.lock().unwrap();
+    if state == MinecraftServerState::Starting {
+        return format!(
+            "HTTP/1.1 409 Conflict\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+            "The server is starting, lists can be changed once it is running"
+        );
+    }
+    if state == MinecraftServerState::Running {
+        if expires.is_some() || level.is_some() {
+            return format!(
+                "HTTP/1.1 409 Conflict\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+                "The server's commands can not set an expiry or level, these can only be set while the server is off"
+            );
+        }
+        let command = match action {
+            "add" => list.add_command(&target, reason.as_deref()),
+            _ => list.remove_command(&target),
+        };
+        return match command_response(&command, LIST_COMMAND_WAIT, &web_sender, &data.server_output) {
+            CommandResponse::Unsent => {
+                "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain\r\nConnection: Close".to_string()
+            }
+            CommandResponse::Queued => format!(
+                "HTTP/1.1 202 Accepted\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+                command_response_json(&command, false, &[])
+            ),
+            CommandResponse::Lines(lines) => format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+                command_response_json(&command, true, &lines)
+            ),
+        };
+    }
+    // The server saves its lists as it stops, which would overwrite changes made before it has exited
+    let exited = match data.child.lock().unwrap().as_mut() {
+        Some(child) => matches!(child.try_wait(), Ok(Some(_))),
+        None => true,
+    };
+    if !exited {
+        return format!(
+            "HTTP/1.1 409 Conflict\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+            "The server is stopping, lists can be changed once it has exited"
+        );
+    }
+
+    let server_error = |error: io::Error| {
+        format!(
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{}",
+            error
+        )
+    };
+    let mut entries = match load_list(list) {
+        Ok(entries) => entries,
+        Err(error) => return server_error(error),
+    };
+    let changed = match action {
+        "add" => {
+            let change = ListChange { reason, expires, level };
+            match add_entry(list, &mut entries, &target, &change, unix_time()) {
+                Ok(changed) => changed,
+                Err(ListError::UnknownPlayer(name)) => {
+                    return format!(
+                        "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{} is not in usercache.json, they need to have joined the server before",
+                        name
+                    )
+                }
+                Err(ListError::InvalidAddress(address)) => {
+                    return bad_request(&format!("{} is not a valid IP address", address))
+                }
+            }
+        }
+        _ => remove_entry(list, &mut entries, &target),
+    };
+    let entries = Json::Array(entries);
+    if changed {
+        if let Err(error) = save_list(list, &entries) {
+            return server_error(error);
+        }
     }
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: Close\r\n\r\n{{\"list\": \"{}\", \"changed\": {}, \"entries\": {}}}",
+        list.name(),
+        changed,
+        entries
+    )
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn sample() {
         assert_eq!(2 + 2, 4, "sample message");
     }
+
+    #[test]
+    fn countdown_parameters() {
+        let query = "delay=300&message=Back_soon";
+        assert_eq!(query_param(query, "delay"), Some("300"));
+        assert_eq!(query_param(query, "message"), Some("Back_soon"));
+        assert_eq!(query_param(query, "wait"), None);
+        assert_eq!(countdown_time(300), "5 minutes");
+        assert_eq!(countdown_time(60), "1 minute");
+        assert_eq!(countdown_time(90), "90 seconds");
+        assert_eq!(countdown_time(1), "1 second");
+    }
+
+    fn job_config(stop_timeout: u64) -> Configuration {
+        Configuration {
+            address: std::net::Ipv4Addr::LOCALHOST,
+            port: 0,
+            web_index: String::new(),
+            root_location: String::new(),
+            jar_name: String::new(),
+            gen_args: None,
+            min_ram: String::new(),
+            max_ram: String::new(),
+            web_log: false,
+            verbosity: crate::functions::configuration::Verbosity::None,
+            stop_timeout,
+            countdown_warnings: vec![30],
+            schedule: Vec::new(),
+            backup_location: String::new(),
+            crash_limit: 0,
+            crash_window: 0,
+            crash_backoff: 0,
+            list_interval: 0,
+            performance_interval: 0,
+            rcon_port: 0,
+            rcon_password: String::new(),
+            query_responder: false,
+            placeholder: false,
+            placeholder_motd: String::new(),
+            placeholder_message: String::new(),
+            flavor: Arc::new(crate::functions::minecraft_related::Vanilla),
+        }
+    }
+
+    fn job_stages(data: &shared_data::ServerSharedData, id: u32) -> Vec<String> {
+        let jobs = data.jobs.lock().unwrap();
+        let job = jobs.iter().find(|job| job.id == id).unwrap();
+        job.stages.iter().map(|(stage, _)| stage.name().to_string()).collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn job_forces_a_stuck_server_and_follows_the_restart() {
+        let data = shared_data::ServerSharedData::new();
+        // A server that ignores both `stop` and SIGTERM
+        let child = Command::new("sh")
+            .args(["-c", "trap '' TERM; while true; do sleep 1; done"])
+            .stdin(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        *data.child.lock().unwrap() = Some(child);
+        *data.mcserver_state.lock().unwrap() = MinecraftServerState::Running;
+        data.jobs.lock().unwrap().push_back(Job::new(1, JobAction::Restart, 0, None));
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        // Stands in for the monitor launching the server again
+        let launcher_data = data.clone();
+        let launcher = thread::spawn(move || {
+            while *launcher_data.gen_state.lock().unwrap() != GeneralState::Restart {
+                thread::sleep(Duration::from_millis(50));
+            }
+            *launcher_data.mcserver_state.lock().unwrap() = MinecraftServerState::Starting;
+            thread::sleep(Duration::from_millis(1200));
+            *launcher_data.mcserver_state.lock().unwrap() = MinecraftServerState::Running;
+        });
+        run_job(1, JobAction::Restart, data.clone(), sender, job_config(1));
+        launcher.join().unwrap();
+
+        assert_eq!(receiver.try_recv().unwrap().command, "stop");
+        assert_eq!(
+            job_stages(&data, 1),
+            vec!["queued", "stop_sent", "waiting", "forced_terminate", "forced_kill", "restarting", "restarted"]
+        );
+        assert!(!server_process_running(&data));
+    }
+
+    #[test]
+    fn job_cancelled_during_countdown() {
+        let data = shared_data::ServerSharedData::new();
+        *data.mcserver_state.lock().unwrap() = MinecraftServerState::Running;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let id = begin_job(JobAction::Restart, 60, None, data.clone(), sender, &job_config(1)).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert!(cancel_job(&id.to_string(), data.jobs.clone()).starts_with("HTTP/1.1 202"));
+        let start = Instant::now();
+        while !data.jobs.lock().unwrap().iter().all(|job| job.finished()) {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        assert_eq!(job_stages(&data, id), vec!["queued", "countdown", "cancelled"]);
+        let said = receiver.try_iter().map(|command| command.command).collect::<Vec<String>>();
+        assert_eq!(said.first().map(|say| say.as_str()), Some("say The server is restarting in 1 minute"));
+        assert_eq!(said.last().map(|say| say.as_str()), Some("say The server is no longer restarting"));
+        assert!(*data.gen_state.lock().unwrap() == GeneralState::Running);
+        // Once cancelled there is nothing left to cancel
+        assert!(cancel_job(&id.to_string(), data.jobs.clone()).starts_with("HTTP/1.1 409"));
+    }
+
+    #[test]
+    fn probe_failures_are_escaped() {
+        let failures = vec!["The server did not answer a status ping: \"C:\\server\" was not found".to_string()];
+        let response = probe_response(false, "\"ready\": false", &failures);
+        let (headers, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(headers.starts_with("HTTP/1.1 503") && headers.contains("application/json"));
+        assert!(Json::parse(body).is_some());
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("A+Minecraft%20Server%3A%20%C2%A7a%E2%98%83"), "A Minecraft Server: §a☃");
+        assert_eq!(percent_decode("my_world"), "my_world");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+
+    #[test]
+    fn metrics_exposition() {
+        let data = shared_data::ServerSharedData::new();
+        *data.mcserver_state.lock().unwrap() = MinecraftServerState::Running;
+        data.push_output("[12:00:00 INFO]: Done".to_string(), OutputStream::Stdout);
+        let response = get_metrics(&data);
+        let exposition = &response[response.find("\r\n\r\n").unwrap() + 4..];
+        assert!(exposition.contains("# TYPE minecraft_players_online gauge\nminecraft_players_online 0\n"));
+        assert!(exposition.contains("minecraft_server_state{state=\"running\"} 1\n"));
+        assert!(exposition.contains("minecraft_server_state{state=\"off\"} 0\n"));
+        assert!(exposition.contains("minecraft_console_lines_total{stream=\"stdout\"} 1\n"));
+        assert!(!exposition.contains("minecraft_tps"));
+    }
+
+    #[test]
+    fn health_probe() {
+        let data = shared_data::ServerSharedData::new();
+        *data.mcserver_state.lock().unwrap() = MinecraftServerState::Running;
+        let minute_ago = Instant::now() - Duration::from_secs(60);
+        data.heartbeats.lock().unwrap().scheduler = Some(Instant::now());
+        data.heartbeats.lock().unwrap().command_written = Some(minute_ago);
+        data.heartbeats.lock().unwrap().output_read = Some(Instant::now());
+        assert!(get_health(&data).starts_with("HTTP/1.1 200 OK"));
+        data.heartbeats.lock().unwrap().output_read = Some(minute_ago - Duration::from_secs(1));
+        data.heartbeats.lock().unwrap().input = Some(minute_ago);
+        let response = get_health(&data);
+        assert!(response.starts_with("HTTP/1.1 503"));
+        assert!(response.contains("The input thread has not run for 60 seconds"));
+        assert!(response.contains("No output has been read"));
+    }
 }
@@ -1,28 +1,648 @@
 // TODO Documentation
+use super::log_parser::LogLevel;
+use super::properties::ServerProperties;
+use super::scheduler::ScheduledTask;
+use super::system_metrics::SystemSample;
 use std::{
-    collections::VecDeque,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, VecDeque},
+    net::TcpListener,
+    process::Child,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    time::Instant,
 };
 
+/// Lines of console output, newest first, with the number of each line and the stream it was written to.
+pub type ConsoleBuffer = Arc<Mutex<VecDeque<(u32, String, OutputStream)>>>;
+
+/// A `list` whose names are printed on the lines after it, the number of players listed and the names read so far.
+pub type PendingList = Arc<Mutex<Option<(u32, Vec<String>)>>>;
+
+/// The number of lines of console output that are kept.
+const SERVER_OUTPUT_LINES: usize = 1000;
+
 #[derive(Clone)]
 pub struct ServerSharedData {
-    pub server_output: Arc<Mutex<VecDeque<(u32, String)>>>,
+    pub server_output: ConsoleBuffer,
     pub current_player_count: Arc<Mutex<u32>>,
     pub current_players: Arc<Mutex<Vec<String>>>,
     pub max_player_count: Arc<Mutex<u32>>,
     pub mcserver_state: Arc<Mutex<MinecraftServerState>>,
     pub gen_state: Arc<Mutex<GeneralState>>,
+    /// The running Minecraft server process, stdin and stdout are owned by the input and output threads.
+    pub child: Arc<Mutex<Option<Child>>>,
+    pub jobs: Arc<Mutex<VecDeque<Job>>>,
+    pub schedule: Arc<Mutex<Vec<ScheduledTask>>>,
+    pub crashes: Arc<Mutex<Vec<CrashReport>>>,
+    /// Progress of the current launch of the Minecraft server.
+    pub startup: Arc<Mutex<StartupProgress>>,
+    /// Previous starts, oldest first, these are loaded from and saved to disk.
+    pub startup_history: Arc<Mutex<Vec<StartupRecord>>>,
+    /// Chat messages, oldest first, these are loaded from and saved to disk.
+    pub chat: Arc<Mutex<VecDeque<ChatMessage>>>,
+    /// Every player that has joined the server, these are loaded from and saved to disk.
+    pub player_history: Arc<Mutex<Vec<PlayerRecord>>>,
+    /// Set when the player history has changed since it was last saved.
+    pub player_history_changed: Arc<Mutex<bool>>,
+    pub pending_list: PendingList,
+    /// Times that the players online differed from the server's `list`, oldest first.
+    pub player_drift: Arc<Mutex<VecDeque<PlayerDrift>>>,
+    /// Things that happened to players in game, oldest first, followed through `/api/events`.
+    pub events: Arc<Mutex<VecDeque<GameEvent>>>,
+    /// Warnings and errors from the server's output, repeats are counted rather than kept.
+    pub alerts: Arc<Mutex<Vec<Alert>>>,
+    pub lag: Arc<Mutex<LagStats>>,
+    pub performance: Arc<Mutex<VecDeque<PerformanceSample>>>,
+    pub system: Arc<Mutex<VecDeque<SystemSample>>>,
+    /// Totals since the monitor started, reported through `/metrics`.
+    pub counters: Arc<Mutex<MonitorCounters>>,
+    /// When each of the monitor's threads last showed that it was running, checked by `/healthz`.
+    pub heartbeats: Arc<Mutex<Heartbeats>>,
+    /// The game port while the monitor holds it for the placeholder, taken back before each launch.
+    pub placeholder: Arc<Mutex<Option<TcpListener>>>,
+    /// The properties as they were when the server finished starting, to tell which edits need a restart.
+    pub loaded_properties: Arc<Mutex<Option<ServerProperties>>>,
 }
 
 impl ServerSharedData {
     pub fn new() -> ServerSharedData {
         ServerSharedData {
-            server_output: Arc::new(Mutex::new(VecDeque::<(u32, String)>::new())),
+            server_output: Arc::new(Mutex::new(VecDeque::<(u32, String, OutputStream)>::new())),
             current_player_count: Arc::new(Mutex::new(0)),
             current_players: Arc::new(Mutex::new(Vec::<String>::new())),
             max_player_count: Arc::new(Mutex::new(0)),
             mcserver_state: Arc::new(Mutex::new(MinecraftServerState::Starting)),
             gen_state: Arc::new(Mutex::new(GeneralState::Running)),
+            child: Arc::new(Mutex::new(None)),
+            jobs: Arc::new(Mutex::new(VecDeque::<Job>::new())),
+            schedule: Arc::new(Mutex::new(Vec::<ScheduledTask>::new())),
+            crashes: Arc::new(Mutex::new(Vec::<CrashReport>::new())),
+            startup: Arc::new(Mutex::new(StartupProgress::new())),
+            startup_history: Arc::new(Mutex::new(Vec::<StartupRecord>::new())),
+            chat: Arc::new(Mutex::new(VecDeque::<ChatMessage>::new())),
+            player_history: Arc::new(Mutex::new(Vec::<PlayerRecord>::new())),
+            player_history_changed: Arc::new(Mutex::new(false)),
+            pending_list: Arc::new(Mutex::new(None)),
+            player_drift: Arc::new(Mutex::new(VecDeque::<PlayerDrift>::new())),
+            events: Arc::new(Mutex::new(VecDeque::<GameEvent>::new())),
+            alerts: Arc::new(Mutex::new(Vec::<Alert>::new())),
+            lag: Arc::new(Mutex::new(LagStats::default())),
+            performance: Arc::new(Mutex::new(VecDeque::<PerformanceSample>::new())),
+            system: Arc::new(Mutex::new(VecDeque::<SystemSample>::new())),
+            counters: Arc::new(Mutex::new(MonitorCounters::new())),
+            heartbeats: Arc::new(Mutex::new(Heartbeats::default())),
+            placeholder: Arc::new(Mutex::new(None)),
+            loaded_properties: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The number that the next line of console output will be stored under.
+    pub fn next_line_number(&self) -> u32 {
+        match self.server_output.lock().unwrap().front() {
+            Some((num, _, _)) => num + 1,
+            None => 0,
+        }
+    }
+
+    /// Adds a line to the console output and returns the number it was stored under.
+    pub fn push_output(&self, line: String, stream: OutputStream) -> u32 {
+        let mut output = self.server_output.lock().unwrap();
+        let num = match output.front() {
+            Some((num, _, _)) => num + 1,
+            None => 0,
+        };
+        output.push_front((num, line, stream));
+        output.truncate(SERVER_OUTPUT_LINES);
+        let mut counters = self.counters.lock().unwrap();
+        match stream {
+            OutputStream::Stdout => counters.stdout_lines += 1,
+            OutputStream::Stderr => counters.stderr_lines += 1,
+        }
+        num
+    }
+}
+
+impl Default for ServerSharedData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Running totals of what the monitor has done.
+#[derive(Clone, Debug)]
+pub struct MonitorCounters {
+    pub started: Instant,
+    /// When the current Minecraft server process was launched, if there is one.
+    pub launched: Option<Instant>,
+    pub launches: u64,
+    pub stdout_lines: u64,
+    pub stderr_lines: u64,
+    pub commands_sent: u64,
+    /// Responses to web requests by their status code.
+    pub responses: BTreeMap<u16, u64>,
+}
+
+impl MonitorCounters {
+    pub fn new() -> MonitorCounters {
+        MonitorCounters {
+            started: Instant::now(),
+            launched: None,
+            launches: 0,
+            stdout_lines: 0,
+            stderr_lines: 0,
+            commands_sent: 0,
+            responses: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for MonitorCounters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The last times that the scheduler and input threads went around their loops, and that the output thread read a
+/// line and the input thread wrote a command.
+#[derive(Clone, Debug, Default)]
+pub struct Heartbeats {
+    pub scheduler: Option<Instant>,
+    pub input: Option<Instant>,
+    pub output_read: Option<Instant>,
+    pub command_written: Option<Instant>,
+}
+
+/// A shutdown or restart that is carried out in the background, the progress is reported through `/api/jobs/<id>`.
+#[derive(Clone)]
+pub struct Job {
+    pub id: u32,
+    pub action: JobAction,
+    pub started: Instant,
+    /// Every stage the job has reached along with the seconds since the job started.
+    pub stages: Vec<(JobStage, f64)>,
+    /// Seconds that players are warned for before the server is stopped.
+    pub delay: u64,
+    pub message: Option<String>,
+    /// Set to stop a job that is still counting down.
+    pub cancelled: bool,
+}
+
+impl Job {
+    pub fn new(id: u32, action: JobAction, delay: u64, message: Option<String>) -> Job {
+        Job {
+            id,
+            action,
+            started: Instant::now(),
+            stages: vec![(JobStage::Queued, 0.0)],
+            delay,
+            message,
+            cancelled: false,
+        }
+    }
+
+    pub fn stage(&self) -> JobStage {
+        self.stages.last().unwrap().0
+    }
+
+    pub fn finished(&self) -> bool {
+        matches!(
+            self.stage(),
+            JobStage::Restarted | JobStage::Stopped | JobStage::Failed | JobStage::Cancelled
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum JobAction {
+    Shutdown,
+    Restart,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum JobStage {
+    Queued,
+    Countdown,
+    Saving,
+    Cancelled,
+    StopSent,
+    Waiting,
+    Terminated,
+    Killed,
+    Restarting,
+    Restarted,
+    Stopped,
+    Failed,
+}
+
+impl JobStage {
+    pub fn name(&self) -> &str {
+        match self {
+            JobStage::Queued => "queued",
+            JobStage::Countdown => "countdown",
+            JobStage::Saving => "saving",
+            JobStage::Cancelled => "cancelled",
+            JobStage::StopSent => "stop_sent",
+            JobStage::Waiting => "waiting",
+            JobStage::Terminated => "forced_terminate",
+            JobStage::Killed => "forced_kill",
+            JobStage::Restarting => "restarting",
+            JobStage::Restarted => "restarted",
+            JobStage::Stopped => "stopped",
+            JobStage::Failed => "failed",
+        }
+    }
+}
+
+/// Details of the Minecraft server exiting without being asked to.
+#[derive(Clone)]
+pub struct CrashReport {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub exit_code: Option<i32>,
+    /// How the process exited, this includes the signal if it was killed.
+    pub status: String,
+    /// The last lines of console output before the crash, oldest first.
+    pub lines: Vec<String>,
+}
+
+/// How far the Minecraft server has got through starting, reported through `/api/startup`.
+#[derive(Clone)]
+pub struct StartupProgress {
+    pub started: Instant,
+    pub stage: StartupStage,
+    /// How much of the current stage is complete, only preparing the spawn area reports this.
+    pub stage_percent: Option<u32>,
+    /// Seconds that the server took to start, once it has.
+    pub duration: Option<f64>,
+}
+
+impl StartupProgress {
+    pub fn new() -> StartupProgress {
+        StartupProgress {
+            started: Instant::now(),
+            stage: StartupStage::Launching,
+            stage_percent: None,
+            duration: None,
+        }
+    }
+
+    /// Moves on to a later stage, stages that are printed out of order never move the progress backwards.
+    pub fn advance(&mut self, stage: StartupStage, stage_percent: Option<u32>) {
+        if stage > self.stage {
+            self.stage = stage;
+            self.stage_percent = stage_percent;
+        } else if stage == self.stage && stage_percent.is_some() {
+            self.stage_percent = stage_percent;
+        }
+    }
+
+    /// An estimate of how much of the whole start is complete.
+    pub fn percent(&self) -> u32 {
+        let next = match self.stage {
+            StartupStage::Done => return 100,
+            StartupStage::PreparingSpawn => 100,
+            _ => self.stage.percent() + 5,
+        };
+        let stage = self.stage.percent();
+        stage + (next - stage) * self.stage_percent.unwrap_or(0).min(100) / 100
+    }
+}
+
+impl Default for StartupProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The milestones that the Minecraft server prints while starting, in the order that they happen.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum StartupStage {
+    /// The process has been started but has not printed a milestone yet.
+    Launching,
+    LoadingLibraries,
+    LoadingProperties,
+    LoadingPlugins,
+    LoadingDatapacks,
+    PreparingLevel,
+    PreparingSpawn,
+    Done,
+}
+
+impl StartupStage {
+    pub fn name(&self) -> &str {
+        match self {
+            StartupStage::Launching => "launching",
+            StartupStage::LoadingLibraries => "loading_libraries",
+            StartupStage::LoadingProperties => "loading_properties",
+            StartupStage::LoadingPlugins => "loading_plugins",
+            StartupStage::LoadingDatapacks => "loading_datapacks",
+            StartupStage::PreparingLevel => "preparing_level",
+            StartupStage::PreparingSpawn => "preparing_spawn",
+            StartupStage::Done => "done",
+        }
+    }
+
+    /// Roughly how much of a start is complete once the stage is reached, most of the time is spent on the spawn area.
+    fn percent(&self) -> u32 {
+        match self {
+            StartupStage::Launching => 0,
+            StartupStage::LoadingLibraries => 5,
+            StartupStage::LoadingProperties => 10,
+            StartupStage::LoadingPlugins => 15,
+            StartupStage::LoadingDatapacks => 25,
+            StartupStage::PreparingLevel => 40,
+            StartupStage::PreparingSpawn => 45,
+            StartupStage::Done => 100,
+        }
+    }
+}
+
+/// A completed start of the Minecraft server.
+#[derive(Clone)]
+pub struct StartupRecord {
+    /// Seconds since the Unix epoch that the server finished starting.
+    pub time: u64,
+    /// Seconds from launching the process until the server had started.
+    pub duration: f64,
+    /// The time that the server reported in its `Done (12.345s)!` message.
+    pub reported: Option<f64>,
+}
+
+/// Everything that is known about a player that has joined the server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerRecord {
+    pub name: String,
+    pub uuid: Option<String>,
+    /// The address of the player's most recent login.
+    pub ip: Option<String>,
+    /// The most recent sessions, oldest first.
+    pub sessions: VecDeque<PlayerSession>,
+    /// Seconds played in finished sessions, including those that are no longer kept.
+    pub playtime: u64,
+    /// The number of times the player has been seen dying.
+    pub deaths: u32,
+    /// The titles of the advancements the player has been seen earning.
+    pub advancements: Vec<String>,
+}
+
+impl PlayerRecord {
+    pub fn new(name: &str) -> PlayerRecord {
+        PlayerRecord {
+            name: name.to_string(),
+            uuid: None,
+            ip: None,
+            sessions: VecDeque::new(),
+            playtime: 0,
+            deaths: 0,
+            advancements: Vec::new(),
+        }
+    }
+
+    pub fn online(&self) -> bool {
+        self.sessions.back().is_some_and(|session| session.left.is_none())
+    }
+
+    /// Seconds played including the current session.
+    pub fn total_playtime(&self, now: u64) -> u64 {
+        match self.sessions.back() {
+            Some(session) if session.left.is_none() => self.playtime + now.saturating_sub(session.joined),
+            _ => self.playtime,
+        }
+    }
+
+    /// Seconds since the Unix epoch that the player was last online.
+    pub fn last_seen(&self, now: u64) -> Option<u64> {
+        self.sessions.back().map(|session| session.left.unwrap_or(now))
+    }
+}
+
+/// The time between a player joining and leaving.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerSession {
+    /// Seconds since the Unix epoch.
+    pub joined: u64,
+    pub left: Option<u64>,
+    /// Why the player's connection was lost, such as `Disconnected` or `Timed out`.
+    pub reason: Option<String>,
+    pub ip: Option<String>,
+}
+
+/// A warning or error printed by the server, along with any stack trace that followed it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alert {
+    pub id: u32,
+    pub level: LogLevel,
+    /// The message as it was first printed.
+    pub message: String,
+    /// The message with its numbers removed, repeats of an alert share this.
+    pub key: String,
+    pub thread: Option<String>,
+    pub logger: Option<String>,
+    /// The lines that followed the latest occurrence, such as a stack trace.
+    pub trace: Vec<String>,
+    pub count: u32,
+    /// Seconds since the Unix epoch.
+    pub first_seen: u64,
+    pub last_seen: u64,
+    /// Set while the lines that follow are still being added to the trace.
+    pub open: bool,
+}
+
+/// Totals from the server's `Can't keep up!` warnings.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LagStats {
+    pub count: u32,
+    pub skipped_ms: u64,
+    pub skipped_ticks: u64,
+    /// The most that the server fell behind by at once, in milliseconds.
+    pub max_ms: u64,
+    /// Seconds since the Unix epoch.
+    pub last: Option<u64>,
+}
+
+/// A measurement of how quickly the server was ticking.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerformanceSample {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub tps: Option<f64>,
+    /// Milliseconds per tick.
+    pub mspt: Option<f64>,
+    pub source: PerformanceSource,
+}
+
+/// Where a performance sample came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PerformanceSource {
+    /// Paper's and Spigot's `tps` and `mspt` commands.
+    Paper,
+    /// Forge's `forge tps` command.
+    Forge,
+    /// The results of `debug start` and `debug stop`.
+    Profiler,
+    /// The ticks that were skipped in `Can't keep up!` warnings.
+    Lag,
+}
+
+impl PerformanceSource {
+    pub fn name(&self) -> &str {
+        match self {
+            PerformanceSource::Paper => "paper",
+            PerformanceSource::Forge => "forge",
+            PerformanceSource::Profiler => "profiler",
+            PerformanceSource::Lag => "lag",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PerformanceSource> {
+        match name {
+            "paper" => Some(PerformanceSource::Paper),
+            "forge" => Some(PerformanceSource::Forge),
+            "profiler" => Some(PerformanceSource::Profiler),
+            "lag" => Some(PerformanceSource::Lag),
+            _ => None,
+        }
+    }
+}
+
+/// Something that happened to a player in game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameEvent {
+    /// Increases by one for each event, so that new events can be asked for.
+    pub id: u64,
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub player: String,
+    pub kind: GameEventKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEventKind {
+    Join,
+    Leave,
+    Death {
+        /// A short name for how the player died, such as `slain` or `fell`.
+        cause: String,
+        /// The player or mob responsible, if there was one.
+        killer: Option<String>,
+        /// The death message as it was printed.
+        message: String,
+    },
+    Advancement {
+        /// `advancement`, `challenge`, `goal` or `achievement` on servers before 1.12.
+        kind: String,
+        title: String,
+    },
+}
+
+impl GameEventKind {
+    pub fn name(&self) -> &str {
+        match self {
+            GameEventKind::Join => "join",
+            GameEventKind::Leave => "leave",
+            GameEventKind::Death { .. } => "death",
+            GameEventKind::Advancement { .. } => "advancement",
+        }
+    }
+}
+
+/// A difference between the players that were counted from joins and leaves and those listed by the server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerDrift {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    /// The number of players that were counted as online.
+    pub tracked: u32,
+    /// The number of players that the server listed.
+    pub listed: u32,
+    /// Players that were listed but not counted as online.
+    pub added: Vec<String>,
+    /// Players that were counted as online but not listed.
+    pub removed: Vec<String>,
+}
+
+/// A message sent by a player, or by the console with `say`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChatMessage {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub player: String,
+    pub message: String,
+    pub kind: ChatKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChatKind {
+    /// `<Player> message`
+    Chat,
+    /// `* Player message`, sent with `/me`.
+    Emote,
+    /// `[Player] message`, sent with `/say`.
+    Say,
+}
+
+impl ChatKind {
+    pub fn name(&self) -> &str {
+        match self {
+            ChatKind::Chat => "chat",
+            ChatKind::Emote => "emote",
+            ChatKind::Say => "say",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ChatKind> {
+        match name {
+            "chat" => Some(ChatKind::Chat),
+            "emote" => Some(ChatKind::Emote),
+            "say" => Some(ChatKind::Say),
+            _ => None,
+        }
+    }
+}
+
+/// A command waiting to be written to the Minecraft server's stdin by the input thread.
+pub struct ConsoleCommand {
+    pub command: String,
+    /// If set, receives the number of the first console line that was produced after the command was written.
+    pub written: Option<Sender<u32>>,
+}
+
+impl ConsoleCommand {
+    pub fn new(command: &str) -> ConsoleCommand {
+        ConsoleCommand {
+            command: command.to_string(),
+            written: None,
+        }
+    }
+
+    /// Creates a command along with a receiver that is notified once the command reaches the server.
+    pub fn with_receipt(command: &str) -> (ConsoleCommand, Receiver<u32>) {
+        let (sender, receiver) = mpsc::channel();
+        (
+            ConsoleCommand {
+                command: command.to_string(),
+                written: Some(sender),
+            },
+            receiver,
+        )
+    }
+}
+
+/// The stream of the Minecraft server process that a line of output was read from.
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputStream {
+    Stdout,
+    /// JVM errors and stack traces that are not written to the server's log.
+    Stderr,
+}
+
+impl OutputStream {
+    pub fn name(&self) -> &str {
+        match self {
+            OutputStream::Stdout => "stdout",
+            OutputStream::Stderr => "stderr",
         }
     }
 }
@@ -35,9 +655,22 @@ pub enum MinecraftServerState {
     Eula
 }
 
+impl MinecraftServerState {
+    pub fn name(&self) -> &str {
+        match self {
+            MinecraftServerState::Off => "off",
+            MinecraftServerState::Starting => "starting",
+            MinecraftServerState::Running => "running",
+            MinecraftServerState::Eula => "eula",
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum GeneralState {
     Running,
+    /// A shutdown or restart job is stopping the Minecraft server.
+    Stopping,
     ShutDown,
     Restart,
 }
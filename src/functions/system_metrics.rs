@@ -0,0 +1,189 @@
+//! Resource use of the Minecraft server's process and of the host, read from `/proc`.
+//!
+//! The process is sampled through `/proc/<pid>/stat`, `status`, `io` and `fd`, the host through `/proc/loadavg`,
+//! `/proc/meminfo` and `df` for the world's directory. Anything that can not be read is left out of the sample.
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use super::minecraft_related::server_property;
+use super::scheduler::unix_time;
+
+/// Seconds between each sample.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The number of samples that are kept, an hour of them.
+pub const SYSTEM_HISTORY: usize = 360;
+
+/// The clock ticks per second that `/proc/<pid>/stat` counts cpu time in, this is 100 on all common kernels.
+const CLOCK_TICKS: f64 = 100.0;
+
+/// The resource use of the Minecraft server's process.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProcessSample {
+    pub pid: u32,
+    /// Resident memory in bytes.
+    pub rss: u64,
+    /// Percent of a single core used since the previous sample, above 100 when more than one core is used.
+    pub cpu: Option<f64>,
+    pub threads: u32,
+    pub open_files: u32,
+    /// Bytes read from and written to disk since the process started.
+    pub read_bytes: Option<u64>,
+    pub write_bytes: Option<u64>,
+}
+
+/// The resource use of the host and of the server at one time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SystemSample {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    pub process: Option<ProcessSample>,
+    /// The load averages over 1, 5 and 15 minutes.
+    pub load: Option<(f64, f64, f64)>,
+    /// Memory in bytes.
+    pub memory_total: Option<u64>,
+    pub memory_available: Option<u64>,
+    /// Space in bytes on the disk holding the world.
+    pub disk_total: Option<u64>,
+    pub disk_free: Option<u64>,
+}
+
+/// Takes samples, remembering the cpu time of the previous sample to work out the cpu use between them.
+#[derive(Default)]
+pub struct SystemSampler {
+    previous: Option<(u32, u64, Instant)>,
+}
+
+impl SystemSampler {
+    pub fn new() -> SystemSampler {
+        SystemSampler { previous: None }
+    }
+
+    /// Samples the host and, while it is running, the process with the id `pid`.
+    pub fn sample(&mut self, pid: Option<u32>, world: &Path) -> SystemSample {
+        let process = pid.and_then(|pid| self.sample_process(pid));
+        let (memory_total, memory_available) = fs::read_to_string("/proc/meminfo")
+            .ok()
+            .map_or((None, None), |meminfo| parse_meminfo(&meminfo));
+        let (disk_total, disk_free) = Command::new("df")
+            .arg("-Pk")
+            .arg(world)
+            .output()
+            .ok()
+            .and_then(|output| parse_df(&String::from_utf8_lossy(&output.stdout)))
+            .map_or((None, None), |(total, free)| (Some(total), Some(free)));
+        SystemSample {
+            time: unix_time(),
+            process,
+            load: fs::read_to_string("/proc/loadavg").ok().and_then(|loadavg| parse_loadavg(&loadavg)),
+            memory_total,
+            memory_available,
+            disk_total,
+            disk_free,
+        }
+    }
+
+    fn sample_process(&mut self, pid: u32) -> Option<ProcessSample> {
+        let proc = format!("/proc/{}", pid);
+        let cpu_ticks = parse_stat(&fs::read_to_string(format!("{}/stat", proc)).ok()?)?;
+        let status = fs::read_to_string(format!("{}/status", proc)).ok()?;
+        let now = Instant::now();
+        let cpu = match self.previous {
+            Some((previous_pid, previous_ticks, previous_time)) if previous_pid == pid => {
+                let seconds = now.duration_since(previous_time).as_secs_f64();
+                Some(cpu_ticks.saturating_sub(previous_ticks) as f64 / CLOCK_TICKS / seconds * 100.0)
+            }
+            _ => None,
+        };
+        self.previous = Some((pid, cpu_ticks, now));
+        // Reading io needs the same user as the process, it is left out when it can not be read
+        let io = fs::read_to_string(format!("{}/io", proc)).unwrap_or_default();
+        Some(ProcessSample {
+            pid,
+            rss: status_field(&status, "VmRSS:").unwrap_or(0) * 1024,
+            cpu,
+            threads: status_field(&status, "Threads:").unwrap_or(0) as u32,
+            open_files: fs::read_dir(format!("{}/fd", proc)).map_or(0, |fds| fds.count() as u32),
+            read_bytes: status_field(&io, "read_bytes:"),
+            write_bytes: status_field(&io, "write_bytes:"),
+        })
+    }
+}
+
+/// Reads the cpu time, in clock ticks, used by the process from the contents of `/proc/<pid>/stat`.
+fn parse_stat(stat: &str) -> Option<u64> {
+    // The name of the process is in brackets and can contain spaces, the fields are counted from after it
+    let fields = stat[stat.rfind(')')? + 1..].split_whitespace().collect::<Vec<&str>>();
+    let user = fields.get(11)?.parse::<u64>().ok()?;
+    let system = fields.get(12)?.parse::<u64>().ok()?;
+    Some(user + system)
+}
+
+/// Finds the number after `name` in files of the form `Name:   1234 kB`.
+fn status_field(contents: &str, name: &str) -> Option<u64> {
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix(name))
+        .and_then(|value| value.split_whitespace().next())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Reads the total and available memory, in bytes, from `/proc/meminfo`.
+fn parse_meminfo(meminfo: &str) -> (Option<u64>, Option<u64>) {
+    (
+        status_field(meminfo, "MemTotal:").map(|kb| kb * 1024),
+        status_field(meminfo, "MemAvailable:").map(|kb| kb * 1024),
+    )
+}
+
+/// Reads the three load averages from `/proc/loadavg`.
+fn parse_loadavg(loadavg: &str) -> Option<(f64, f64, f64)> {
+    let mut averages = loadavg.split_whitespace().map(|average| average.parse::<f64>().ok());
+    Some((averages.next()??, averages.next()??, averages.next()??))
+}
+
+/// Reads the total and free space, in bytes, from the output of `df -Pk`.
+fn parse_df(output: &str) -> Option<(u64, u64)> {
+    let fields = output.lines().nth(1)?.split_whitespace().collect::<Vec<&str>>();
+    let total = fields.get(1)?.parse::<u64>().ok()?;
+    let free = fields.get(3)?.parse::<u64>().ok()?;
+    Some((total * 1024, free * 1024))
+}
+
+/// Finds the world's directory from the `level-name` in `server.properties`, the server directory is used when it is
+/// not set or does not exist yet.
+pub fn world_directory() -> String {
+    server_property("level-name")
+        .filter(|name| !name.is_empty() && Path::new(name).is_dir())
+        .unwrap_or_else(|| ".".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proc_files() {
+        let stat = "4242 (java (server)) S 1 4242 4242 0 -1 4194560 91234 0 12 0 1520 340 0 0 20 0 57 0 1234 5000000 \
+                    200000 18446744073709551615";
+        assert_eq!(parse_stat(stat), Some(1860));
+        let status = "Name:\tjava\nVmRSS:\t 2097152 kB\nThreads:\t57\n";
+        assert_eq!(status_field(status, "VmRSS:"), Some(2097152));
+        assert_eq!(status_field(status, "Threads:"), Some(57));
+        assert_eq!(status_field(status, "VmSwap:"), None);
+        assert_eq!(
+            parse_meminfo("MemTotal:       16314640 kB\nMemFree:  1000 kB\nMemAvailable:    8157320 kB\n"),
+            (Some(16314640 * 1024), Some(8157320 * 1024))
+        );
+        assert_eq!(parse_loadavg("0.52 0.58 0.59 2/1024 12345\n"), Some((0.52, 0.58, 0.59)));
+        assert_eq!(
+            parse_df("Filesystem     1024-blocks      Used Available Capacity Mounted on\n/dev/sda1  102400 51200 46080 53% /\n"),
+            Some((102400 * 1024, 46080 * 1024))
+        );
+        assert_eq!(parse_df("df: world: No such file or directory\n"), None);
+    }
+}
@@ -1,133 +1,271 @@
-use std::{fs, io::{BufRead, BufReader, Write}, net::{Ipv4Addr, SocketAddr, TcpListener}, path::Path, sync::mpsc::Sender, thread};
+use std::{
+    fs,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::{SocketAddr, TcpListener},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
 
 use shared_data::{GeneralState, MinecraftServerState};
 
 // Import the functions from the same level file
-use super::{configuration::Verbosity, server_interactions};
-use super::shared_data;
+use super::configuration::{Configuration, Verbosity};
+use super::server_interactions;
+use super::shared_data::{self, ConsoleCommand};
+
+/// The most requests that are answered at once, any more are turned away until one finishes.
+const MAX_REQUESTS: usize = 32;
+
+/// How long a connection has to send its request line.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One of the `MAX_REQUESTS` places, given back when the request's thread finishes, even if it panicked.
+pub(crate) struct RequestSlot(Arc<AtomicUsize>);
+
+impl RequestSlot {
+    pub(crate) fn take(active: &Arc<AtomicUsize>) -> RequestSlot {
+        active.fetch_add(1, Ordering::SeqCst);
+        RequestSlot(active.clone())
+    }
+}
+
+impl Drop for RequestSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
 
 // TODO The names of variables within this function are atrocious
 pub fn handle_connections(
     data: shared_data::ServerSharedData,
-    web_sender: Sender<String>,
-    address: Ipv4Addr,
-    port: u16,
-    root_html: String,
-    verbosity: Verbosity,
+    web_sender: Sender<ConsoleCommand>,
+    config: Configuration,
 ) -> std::io::Result<()> {
-    // loop {
-    let listener = TcpListener::bind((address, port))?;
+    let listener = TcpListener::bind((config.address, config.port))?;
+    // Shutdowns happen in the background, so the listener can't block while waiting for a request
+    listener.set_nonblocking(true)?;
     let data2 = data.clone();
     let sender = web_sender.clone();
+    let active = Arc::new(AtomicUsize::new(0));
     // For each request create a thread to parse request and send contents
     for stream in listener.incoming() {
+        {
+            // The web server is kept between launches, so it only stops along with the monitor
+            if *data.gen_state.lock().unwrap() == GeneralState::ShutDown {
+                break;
+            }
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(50));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        stream.set_nonblocking(false)?;
+        // Requests are answered alongside each other, so one that waits on the server doesn't hold up the rest
+        if active.load(Ordering::SeqCst) >= MAX_REQUESTS {
+            let mut stream = stream;
+            let _ = stream.write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Type: text/plain\r\nConnection: Close");
+            continue;
+        }
+        let slot = RequestSlot::take(&active);
         let data3 = data2.clone();
         let sender = sender.clone();
-        let root_html = root_html.clone();
-        let state_data = data3.clone();
-        let handle = thread::spawn(move || {
+        let config = config.clone();
+        thread::spawn(move || {
+            let _slot = slot;
             let data4 = data3.clone();
             let sender = sender.clone();
-            let mut stream = stream.unwrap();
+            let mut stream = stream;
+            // Connections that never send a request are dropped instead of keeping the thread forever
+            if stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_err() {
+                return;
+            }
             let mut reader = BufReader::new(stream.try_clone().unwrap());
             let mut line = String::new();
-            reader.read_line(&mut line).unwrap();
-            match line.find("/") {
+            if reader.read_line(&mut line).is_err() {
+                return;
+            }
+            match (line.find('/'), line.find(" HTTP")) {
                 // Every proper request line should contain a forward slash
-                Some(start) => {
-                    let request = &line[start..line.find("HTTP").unwrap() - 1];
-                    stream
-                        .write_all(generate_response(request, data4, sender, root_html, verbosity, stream.peer_addr().unwrap()).as_bytes())
-                        .unwrap();
+                (Some(start), Some(end)) if start < end => {
+                    let request = &line[start..end];
+                    let peer_address = match stream.peer_addr() {
+                        Ok(address) => address,
+                        Err(_) => return,
+                    };
+                    let counters = data4.counters.clone();
+                    let response = generate_response(request, data4, sender, &config, peer_address);
+                    if let Some(status) = response.split(' ').nth(1).and_then(|status| status.parse::<u16>().ok()) {
+                        *counters.lock().unwrap().responses.entry(status).or_insert(0) += 1;
+                    }
+                    let _ = stream.write_all(response.as_bytes());
                 }
-                None => {
-                    if verbosity == Verbosity::Web || verbosity == Verbosity::MineWeb {
+                _ => {
+                    if config.verbosity == Verbosity::Web || config.verbosity == Verbosity::MineWeb {
                         println!("\x1b[0;33m[Request]:\x1b[0m Empty Request Received");
                     }
                 }
             }
         });
-        handle.join().unwrap();
-        // Once the current request has finished check for shutdown
-        let state = state_data.gen_state.lock().unwrap();
-        if *state == GeneralState::ShutDown || *state == GeneralState::Restart {
-            break;
-        }
     }
     Ok(())
-    // }
 }
 
 fn generate_response(
     request: &str,
     data: shared_data::ServerSharedData,
-    web_sender: Sender<String>,
-    root_html: String,
-    verbosity: Verbosity,
+    web_sender: Sender<ConsoleCommand>,
+    config: &Configuration,
     peer_address: SocketAddr
 ) -> String {
     let default_http_header = "HTTP/1.1 200 OK\r\nConnection: Close\r\nContent-Type:";
     let headers404 = "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nConnection: Close";
-    if verbosity == Verbosity::Web || verbosity == Verbosity::MineWeb {
-        println!("\x1b[0;33m[\x1b[32m{}\x1b[0;33m]:\x1b[0m {}",  peer_address.to_string(), request);
+    if config.verbosity == Verbosity::Web || config.verbosity == Verbosity::MineWeb {
+        println!("\x1b[0;33m[\x1b[32m{}\x1b[0;33m]:\x1b[0m {}",  peer_address, request);
     }
+    let (path, query) = match request.find('?') {
+        Some(loc) => (&request[..loc], &request[loc + 1..]),
+        None => (request, ""),
+    };
     { // Reduce the space that the shared data is in scope, this should be replaced with try locks later
         let ref_state;
         {
             let start_data = data.clone();
             let start_state = start_data.mcserver_state.lock().unwrap();
-            ref_state = start_state.clone();
+            ref_state = *start_state;
+        }
+        if path.len() > 10 && &path[0..10] == "/api/jobs/" {
+            // Jobs are followed through restarts, so they are available in every state
+            return match path[10..].strip_suffix("/cancel") {
+                Some(id) => server_interactions::cancel_job(id, data.jobs),
+                None => server_interactions::get_job(&path[10..], data.jobs),
+            };
+        }
+        if path == "/api/startup" {
+            // The starting page follows the progress through this
+            return server_interactions::get_startup(&data);
+        }
+        // Probes from supervisors need an answer whatever state the server is in
+        if path == "/healthz" {
+            return server_interactions::get_health(&data);
+        }
+        if path == "/readyz" {
+            return server_interactions::get_readiness(&data);
+        }
+        if path == "/metrics" {
+            // Scrapes carry on while the server is starting or waiting for the EULA
+            return server_interactions::get_metrics(&data);
+        }
+        if path == "/api/alerts" {
+            // Problems while starting are the ones most worth seeing
+            return server_interactions::get_alerts(query, data.alerts, data.lag);
+        }
+        // Properties are most often changed before the first start or while waiting for the EULA
+        if path == "/api/properties" {
+            return server_interactions::get_properties(&data);
+        }
+        if path == "/api/properties/set" {
+            return server_interactions::set_properties(query, &data);
+        }
+        if path.len() > 11 && &path[0..11] == "/api/lists/" {
+            // Lists are edited in their files while the server is waiting for the EULA
+            return match path[11..].split_once('/') {
+                Some((list, action)) => server_interactions::modify_player_list(list, action, query, data, web_sender),
+                None => server_interactions::get_player_list(&path[11..]),
+            };
         }
         if ref_state == MinecraftServerState::Eula {
-            return match request {
+            return match path {
                 "/api/accept" => {
                     server_interactions::accept_eula(data)
                 },
-                "/api/restart" => server_interactions::restart(data.mcserver_state, data.gen_state, web_sender),
+                "/api/restart" => server_interactions::restart(query, data, web_sender, config),
                 "/eula.css" => format!("{} {}\r\n\r\n{}", default_http_header, get_file_type("eula.css"), get_file_contents("/eula.css")), // This line could be turned into a function of its own
                 "/eula.js" => format!("{} {}\r\n\r\n{}", default_http_header, get_file_type("eula.js"), get_file_contents("/eula.js")),
                 _ => format!("{} {}\r\n\r\n{}", default_http_header, get_file_type("eula.html"), get_file_contents("/eula.html"))
             }
         } else if ref_state == MinecraftServerState::Starting {
             return format!("{} {}\r\n\r\n{}", default_http_header, get_file_type("starting.html"), get_file_contents("/starting.html"))
+        } else if ref_state == MinecraftServerState::Off && path == "/api/send" {
+            // Between launches there is no server to read the command
+            return "HTTP/1.1 409 Conflict\r\nContent-Type: text/plain\r\nConnection: Close".to_string();
         }
     }
-    match request {
+    match path {
         "/" => format!(
             "{} text/html\r\n\r\n{}",
             default_http_header,
-            get_file_contents(root_html.as_str())
+            get_file_contents(config.web_index.as_str())
         ),
         "/api/players" => server_interactions::get_players(
             data.current_player_count,
             data.max_player_count,
             data.current_players,
         ),
+        "/api/drift" => server_interactions::get_player_drift(data.player_drift),
         "/api/console" => server_interactions::get_console(data.server_output),
-        "/api/shutdown" => {
-            server_interactions::shutdown(data.mcserver_state, data.gen_state, web_sender)
+        "/api/shutdown" => server_interactions::shutdown(query, data, web_sender, config),
+        "/api/restart" => server_interactions::restart(query, data, web_sender, config),
+        "/api/crashes" => server_interactions::get_crashes(data.crashes),
+        "/api/chat" => server_interactions::get_chat(query, data.chat),
+        "/api/events" => server_interactions::get_events(query, data.events),
+        "/api/performance" => server_interactions::get_performance(query, data.performance),
+        "/api/ping" => server_interactions::get_ping(query),
+        "/api/query" => server_interactions::get_query(query),
+        "/api/system" => server_interactions::get_system(query, data.system, config),
+        "/api/leaderboard" => server_interactions::get_leaderboard(query, data.player_history),
+        "/api/flavor" => server_interactions::get_flavor(config.flavor.as_ref()),
+        "/api/schedule" => server_interactions::get_schedule(data.schedule),
+        "/api/schedule/add" => server_interactions::add_schedule(query, data.schedule),
+        "/data/send" if query == "stop" => {
+            server_interactions::restart("", data, web_sender, config)
+        }
+        "/api/send" if !query.is_empty() => {
+            // Commands can optionally wait for the server's response with '&wait=<milliseconds>', or be sent through
+            // RCON with '&rcon' to get exactly the server's response
+            let query = &request[9..];
+            if let Some(command) = query.strip_suffix("&rcon") {
+                return server_interactions::send_command_rcon(command);
+            }
+            match query.find("&wait=") {
+                Some(loc) => server_interactions::send_command_wait(
+                    &query[..loc],
+                    &query[loc + 6..],
+                    web_sender,
+                    data.server_output,
+                ),
+                None => server_interactions::send_command(query, web_sender),
+            }
+        }
+        _ if path.len() > 13 && &path[0..13] == "/api/players/" => {
+            server_interactions::get_player(&path[13..], data.player_history)
+        }
+        _ if path.len() > 14 && &path[0..14] == "/api/schedule/" => {
+            server_interactions::modify_schedule(&path[14..], data.schedule)
         }
-        "/api/restart" | "/data/send?stop" => {
-            server_interactions::restart(data.mcserver_state, data.gen_state, web_sender)
-        },
         _ => {
-            if request.len() > 10 as usize && &request[0..10] == "/api/send?" {
-                server_interactions::send_command(&request[9..], web_sender)
-            } else {
-                if Path::new(
-                    format!("../public/{}/{}", get_file_folder(request), &request[1..]).as_str(),
+            if Path::new(
+                format!("../public/{}/{}", get_file_folder(request), &request[1..]).as_str(),
+            )
+            .exists()
+            {
+                format!(
+                    "{} {}\r\n\r\n{}",
+                    default_http_header,
+                    get_file_type(request),
+                    get_file_contents(request)
                 )
-                .exists()
-                {
-                    format!(
-                        "{} {}\r\n\r\n{}",
-                        default_http_header,
-                        get_file_type(request),
-                        get_file_contents(request)
-                    )
-                } else {
-                    headers404.to_string()
-                }
+            } else {
+                headers404.to_string()
             }
         }
     }
@@ -163,7 +301,7 @@ fn get_file_contents(path: &str) -> String {
         get_file_folder(path),
         &path[1..]
     ))
-    .expect(format!("Failed to read file: {}", path).as_str())
+    .unwrap_or_else(|_| panic!("Failed to read file: {}", path))
 }
 
 #[cfg(test)]